@@ -0,0 +1,993 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use std::{panic, thread};
+
+use crate::encoding::Encoding;
+use crate::filter::MessageFilter;
+use crate::format::{OutputFormat, Record};
+use crate::queue::{BoundedQueue, OverflowPolicy, PopError};
+use crate::source::{DebugMessage, MessageSource, SourceEvent, Timestamp};
+use crate::split::ProcessFiles;
+use crate::target::ProcessExclusions;
+use crate::template::Template;
+use crate::winproc::ProcessNameCache;
+
+const ANSI_BLUE: &str = "\x1b[34m";
+const ANSI_DEFAULT_FOREGROUND: &str = "\x1b[39m";
+/// How long the writer thread waits for an event before checking for a due flush.
+const WRITER_IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+fn matches_target_pid(target_pids: Option<&HashSet<u32>>, pid: u32) -> bool {
+    match target_pids {
+        Some(pids) => pids.contains(&pid),
+        None => true,
+    }
+}
+
+/// When buffered output is flushed, see `--flush`. Output is always flushed when
+/// capture ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// After every message.
+    #[default]
+    Every,
+    /// At most this often, and when no messages arrive.
+    Interval(Duration),
+    OnExit,
+}
+
+impl FromStr for FlushPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(millis) = value.strip_prefix("interval=") {
+            return match millis.parse::<u64>() {
+                Ok(millis) if millis > 0 => {
+                    Ok(FlushPolicy::Interval(Duration::from_millis(millis)))
+                }
+                _ => Err(format!(
+                    "Invalid flush interval '{}'. Expected a positive number of milliseconds.",
+                    millis
+                )),
+            };
+        }
+
+        match value {
+            "every" => Ok(FlushPolicy::Every),
+            "on-exit" => Ok(FlushPolicy::OnExit),
+            _ => Err(format!(
+                "Unknown flush policy '{}'. Expected every, interval=<ms> or on-exit.",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CaptureOptions {
+    /// Line layout for text output.
+    pub template: Template,
+    /// Words to highlight in text output of sinks with `highlight` set.
+    pub highlight_words: Vec<String>,
+    pub filter: MessageFilter,
+    pub encoding: Encoding,
+    /// Write each line of a multi-line message as its own text line, see `--split-lines`.
+    pub split_lines: bool,
+    pub flush: FlushPolicy,
+}
+
+/// Counters reported when capture ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// Messages that were written with replacement characters for undecodable bytes.
+    pub replaced_messages: u64,
+    /// Messages discarded because the output queue was full, see `--on-overflow`.
+    pub dropped_messages: u64,
+}
+
+pub enum SinkOutput {
+    Stream(Box<dyn Write + Send>),
+    /// One file per process, see `--split-by-process`.
+    PerProcess(Box<ProcessFiles>),
+}
+
+/// One output destination with its own line format.
+pub struct Sink {
+    pub output: SinkOutput,
+    pub format: OutputFormat,
+    /// Whether `highlight_words` are colored; only set for the console.
+    pub highlight: bool,
+}
+
+pub type SharedTargetPids = Arc<RwLock<HashSet<u32>>>;
+
+pub enum CaptureTarget {
+    All,
+    /// All processes except those matching `--exclude-process` or `--exclude-pid`.
+    AllExcept(ProcessExclusions),
+    StaticPids(HashSet<u32>),
+    SharedPids(SharedTargetPids),
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Windows(u32),
+    Io(io::Error),
+    #[cfg(not(windows))]
+    Unsupported,
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Windows(code) => write!(f, "Windows error {}", code),
+            CaptureError::Io(e) => write!(f, "I/O error: {}", e),
+            #[cfg(not(windows))]
+            CaptureError::Unsupported => {
+                write!(f, "live capture is only supported on Windows")
+            }
+        }
+    }
+}
+
+impl From<u32> for CaptureError {
+    fn from(value: u32) -> Self {
+        CaptureError::Windows(value)
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(value: io::Error) -> Self {
+        CaptureError::Io(value)
+    }
+}
+
+impl CaptureTarget {
+    fn matches(&self, message: &DebugMessage) -> bool {
+        let pid = message.pid;
+        match self {
+            CaptureTarget::All => true,
+            CaptureTarget::AllExcept(exclusions) => {
+                !exclusions.excludes(pid, message.process_name.as_deref())
+            }
+            CaptureTarget::StaticPids(pids) => matches_target_pid(Some(pids), pid),
+            CaptureTarget::SharedPids(pids) => pids
+                .read()
+                .map(|pids| matches_target_pid(Some(&pids), pid))
+                .unwrap_or(false),
+        }
+    }
+}
+
+fn highlight_text(text: &str, words: &[String]) -> String {
+    if words.is_empty() {
+        return text.to_string();
+    }
+
+    let mut highlighted = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < text.len() {
+        let best_match = words
+            .iter()
+            .filter(|word| !word.is_empty())
+            .filter_map(|word| {
+                let end = index.checked_add(word.len())?;
+                if end <= text.len()
+                    && text.is_char_boundary(end)
+                    && text[index..end].eq_ignore_ascii_case(word)
+                {
+                    Some(end)
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|end| end - index);
+
+        if let Some(end) = best_match {
+            highlighted.push_str(ANSI_BLUE);
+            highlighted.push_str(&text[index..end]);
+            highlighted.push_str(ANSI_DEFAULT_FOREGROUND);
+            index = end;
+        } else {
+            let ch = text[index..].chars().next().expect("index is in bounds");
+            highlighted.push(ch);
+            index += ch.len_utf8();
+        }
+    }
+
+    highlighted
+}
+
+/// The message bytes up to the first NUL.
+fn message_bytes(message: &DebugMessage) -> &[u8] {
+    let nul_pos = message
+        .bytes
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(message.bytes.len());
+    &message.bytes[..nul_pos]
+}
+
+fn write_record(record: &Record, sink: &mut Sink, options: &CaptureOptions) -> io::Result<()> {
+    let line = if options.split_lines {
+        sink.format.format_record_lines(record, &options.template)
+    } else {
+        sink.format.format_record(record, &options.template)
+    };
+    let line = match sink.format {
+        OutputFormat::Text if sink.highlight => highlight_text(&line, &options.highlight_words),
+        OutputFormat::Text | OutputFormat::Jsonl | OutputFormat::Csv => line,
+    };
+    let output: &mut dyn Write = match &mut sink.output {
+        SinkOutput::Stream(output) => output,
+        SinkOutput::PerProcess(files) => files.file_for(record.message)?,
+    };
+    writeln!(output, "{}", line)
+}
+
+fn flush_sink(sink: &mut Sink) -> io::Result<()> {
+    match &mut sink.output {
+        SinkOutput::Stream(output) => output.flush(),
+        SinkOutput::PerProcess(files) => files.flush(),
+    }
+}
+
+fn flush_sinks(sinks: &mut [Sink]) -> io::Result<()> {
+    sinks.iter_mut().try_for_each(flush_sink)
+}
+
+/// Flushes every sink that still can be after capture failed, so one broken output
+/// does not lose the lines buffered for the others.
+pub fn flush_remaining(sinks: &mut [Sink]) {
+    for sink in sinks {
+        let _ = flush_sink(sink);
+    }
+}
+
+/// Applies the [`FlushPolicy`] to output written since the last flush.
+struct Flusher {
+    policy: FlushPolicy,
+    last_flush: Instant,
+    pending: bool,
+}
+
+impl Flusher {
+    fn new(policy: FlushPolicy) -> Self {
+        Flusher {
+            policy,
+            last_flush: Instant::now(),
+            pending: false,
+        }
+    }
+
+    fn flush_if_due(&mut self, sinks: &mut [Sink]) -> io::Result<()> {
+        let due = match self.policy {
+            FlushPolicy::Every => true,
+            FlushPolicy::Interval(interval) => self.last_flush.elapsed() >= interval,
+            FlushPolicy::OnExit => false,
+        };
+        if self.pending && due {
+            flush_sinks(sinks)?;
+            self.pending = false;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn written(&mut self, sinks: &mut [Sink]) -> io::Result<()> {
+        self.pending = true;
+        self.flush_if_due(sinks)
+    }
+}
+
+/// Numbers written records and tracks the time since the previous one.
+#[derive(Default)]
+struct Sequence {
+    seq: u64,
+    previous_timestamp: Option<Timestamp>,
+}
+
+impl Sequence {
+    fn next(&mut self, timestamp: Timestamp) -> (u64, i64) {
+        self.seq += 1;
+        let delta_ms = self.previous_timestamp.map_or(0, |previous| {
+            timestamp.epoch_millis() - previous.epoch_millis()
+        });
+        self.previous_timestamp = Some(timestamp);
+        (self.seq, delta_ms)
+    }
+}
+
+/// Writes every matching message and process lifecycle event to all `sinks`.
+pub fn capture_debug_output(
+    source: &mut dyn MessageSource,
+    target: CaptureTarget,
+    sinks: &mut [Sink],
+    options: &CaptureOptions,
+) -> Result<CaptureStats, CaptureError> {
+    let mut sequence = Sequence::default();
+    let mut stats = CaptureStats::default();
+    let mut flusher = Flusher::new(options.flush);
+    loop {
+        match source.next_event()? {
+            SourceEvent::Message(message) => {
+                if !target.matches(&message) {
+                    continue;
+                }
+                let Some(decoded) = options.encoding.decode(message_bytes(&message)) else {
+                    continue;
+                };
+                if decoded.replaced {
+                    stats.replaced_messages += 1;
+                }
+                let text = decoded.text.trim_end();
+                if !options.filter.accepts(&message, text) {
+                    continue;
+                }
+
+                let (seq, delta_ms) = sequence.next(message.timestamp);
+                let record = Record {
+                    message: &message,
+                    text,
+                    seq,
+                    delta_ms,
+                    event: None,
+                };
+                for sink in sinks.iter_mut() {
+                    write_record(&record, sink, options)?;
+                }
+                flusher.written(sinks)?;
+            }
+            SourceEvent::Process(event) => {
+                // Lifecycle records bypass the message filters; they only come from the
+                // scanner that maintains the target PIDs.
+                let message = DebugMessage {
+                    pid: event.pid,
+                    timestamp: event.timestamp,
+                    bytes: Vec::new(),
+                    process_name: event.process_name,
+                };
+                let (seq, delta_ms) = sequence.next(message.timestamp);
+                let record = Record {
+                    message: &message,
+                    text: event.kind.text(),
+                    seq,
+                    delta_ms,
+                    event: Some(event.kind),
+                };
+                for sink in sinks.iter_mut() {
+                    write_record(&record, sink, options)?;
+                }
+                flusher.written(sinks)?;
+            }
+            SourceEvent::Idle => flusher.flush_if_due(sinks)?,
+            SourceEvent::Closed => break,
+        }
+    }
+
+    flush_sinks(sinks)?;
+    Ok(stats)
+}
+
+/// Reads the queue filled by [`capture_in_background`], adding the names of the
+/// processes that wrote the messages.
+struct QueuedEvents<'a> {
+    queue: &'a BoundedQueue<SourceEvent>,
+    names: ProcessNameCache,
+}
+
+impl MessageSource for QueuedEvents<'_> {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        match self.queue.pop(WRITER_IDLE_INTERVAL) {
+            Ok(SourceEvent::Message(mut message)) => {
+                if message.process_name.is_none() {
+                    message.process_name = self.names.name(message.pid);
+                }
+                Ok(SourceEvent::Message(message))
+            }
+            Ok(event) => Ok(event),
+            Err(PopError::Timeout) => Ok(SourceEvent::Idle),
+            Err(PopError::Closed) => Ok(SourceEvent::Closed),
+        }
+    }
+}
+
+fn forward_events(
+    source: &mut dyn MessageSource,
+    queue: &BoundedQueue<SourceEvent>,
+) -> Result<(), CaptureError> {
+    loop {
+        match source.next_event()? {
+            SourceEvent::Closed => return Ok(()),
+            SourceEvent::Idle => continue,
+            event => {
+                if !queue.push(event) {
+                    // The writer stopped early and reports why.
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Like [`capture_debug_output`], but only reads `source` on the calling thread and hands
+/// the events to a writer thread through a queue of `capacity` events, so slow sinks do
+/// not delay the processes being debugged. Process names missing from messages are
+/// looked up on the writer thread. Everything queued is written before returning.
+pub fn capture_in_background(
+    source: &mut dyn MessageSource,
+    target: CaptureTarget,
+    sinks: &mut [Sink],
+    options: &CaptureOptions,
+    capacity: usize,
+    overflow: OverflowPolicy,
+) -> Result<CaptureStats, CaptureError> {
+    // Process lifecycle events are never dropped, so only messages are lost.
+    let queue = BoundedQueue::new(capacity, overflow)
+        .keeping(|event| matches!(event, SourceEvent::Process(_)));
+    let (read_result, write_result) = thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            let mut events = QueuedEvents {
+                queue: &queue,
+                names: ProcessNameCache::default(),
+            };
+            let result = capture_debug_output(&mut events, target, sinks, options);
+            queue.close();
+            result
+        });
+        let read_result = forward_events(source, &queue);
+        queue.close();
+        let write_result = writer.join().unwrap_or_else(|e| panic::resume_unwind(e));
+        (read_result, write_result)
+    });
+
+    let mut stats = write_result?;
+    read_result?;
+    stats.dropped_messages = queue.dropped();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::{self, BufWriter, Write};
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::time::Duration;
+
+    use super::{
+        CaptureError, CaptureOptions, CaptureTarget, FlushPolicy, Sink, SinkOutput,
+        capture_debug_output, capture_in_background, flush_remaining, highlight_text,
+    };
+    use crate::encoding::{CodePage, Encoding};
+    use crate::filter::{FilterRules, MessageFilter};
+    use crate::format::OutputFormat;
+    use crate::queue::OverflowPolicy;
+    use crate::source::{
+        DebugMessage, ProcessEvent, ProcessEventKind, ScriptedSource, SourceEvent, test_timestamp,
+    };
+    use crate::target::ProcessExclusions;
+
+    /// Cloneable in-memory output, so a test can read what a boxed sink wrote.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn sink(&self, format: OutputFormat, highlight: bool) -> Sink {
+            Sink {
+                output: SinkOutput::Stream(Box::new(self.clone())),
+                format,
+                highlight,
+            }
+        }
+
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture_as(
+        source: &mut ScriptedSource,
+        target: CaptureTarget,
+        format: OutputFormat,
+        options: &CaptureOptions,
+    ) -> String {
+        let buffer = SharedBuffer::default();
+        capture_debug_output(source, target, &mut [buffer.sink(format, true)], options).unwrap();
+        buffer.contents()
+    }
+
+    fn capture_with(
+        source: &mut ScriptedSource,
+        target: CaptureTarget,
+        options: &CaptureOptions,
+    ) -> String {
+        capture_as(source, target, OutputFormat::Text, options)
+    }
+
+    fn capture(source: &mut ScriptedSource, target: CaptureTarget, words: &[String]) -> String {
+        let options = CaptureOptions {
+            highlight_words: words.to_vec(),
+            ..CaptureOptions::default()
+        };
+        capture_with(source, target, &options)
+    }
+
+    #[test]
+    fn highlights_matching_words_case_insensitively() {
+        let words = vec!["error".to_string()];
+
+        assert_eq!(
+            highlight_text("An ERROR occurred", &words),
+            "An \x1b[34mERROR\x1b[39m occurred"
+        );
+    }
+
+    #[test]
+    fn highlights_longest_matching_word_first() {
+        let words = vec!["error".to_string(), "error code".to_string()];
+
+        assert_eq!(
+            highlight_text("error code 5", &words),
+            "\x1b[34merror code\x1b[39m 5"
+        );
+    }
+
+    #[test]
+    fn leaves_text_unchanged_without_highlight_words() {
+        assert_eq!(highlight_text("plain output", &[]), "plain output");
+    }
+
+    #[test]
+    fn writes_messages_from_all_processes() {
+        let mut source = ScriptedSource::messages(&[(1, "first\r\n"), (2, "second")]);
+
+        assert_eq!(
+            capture(&mut source, CaptureTarget::All, &[]),
+            "[2026-06-01 09:08:07.006] [1 ?] first\n[2026-06-01 09:08:07.006] [2 ?] second\n"
+        );
+    }
+
+    #[test]
+    fn skips_messages_from_other_pids() {
+        let mut source = ScriptedSource::messages(&[(1, "first"), (2, "second")]);
+        let target = CaptureTarget::StaticPids(HashSet::from([2]));
+
+        assert_eq!(
+            capture(&mut source, target, &[]),
+            "[2026-06-01 09:08:07.006] [2 ?] second\n"
+        );
+    }
+
+    #[test]
+    fn skips_messages_from_excluded_processes() {
+        let message = |pid, name: &str, text: &str| {
+            SourceEvent::Message(DebugMessage {
+                pid,
+                timestamp: test_timestamp(),
+                bytes: text.as_bytes().to_vec(),
+                process_name: Some(name.to_string()),
+            })
+        };
+        let mut source = ScriptedSource::new(vec![
+            message(1, "devenv.exe", "noise"),
+            message(2, "app.exe", "kept"),
+            message(3, "svc.exe", "excluded by pid"),
+        ]);
+        let target = CaptureTarget::AllExcept(ProcessExclusions {
+            names: vec!["DEVENV.EXE".to_string()],
+            pids: HashSet::from([3]),
+        });
+
+        assert_eq!(
+            capture(&mut source, target, &[]),
+            "[2026-06-01 09:08:07.006] [2 app.exe] kept\n"
+        );
+    }
+
+    #[test]
+    fn reads_current_shared_pids() {
+        let pids = Arc::new(RwLock::new(HashSet::from([1])));
+        let mut source = ScriptedSource::messages(&[(1, "before"), (2, "after")]);
+        *pids.write().unwrap() = HashSet::from([2]);
+
+        assert_eq!(
+            capture(&mut source, CaptureTarget::SharedPids(pids), &[]),
+            "[2026-06-01 09:08:07.006] [2 ?] after\n"
+        );
+    }
+
+    #[test]
+    fn stops_message_at_nul_and_replaces_invalid_utf8() {
+        let mut source = ScriptedSource::new(vec![
+            SourceEvent::Idle,
+            SourceEvent::Message(DebugMessage {
+                pid: 1,
+                timestamp: test_timestamp(),
+                bytes: vec![0xff, 0xfe],
+                process_name: None,
+            }),
+            SourceEvent::Message(DebugMessage {
+                pid: 1,
+                timestamp: test_timestamp(),
+                bytes: b"text\0garbage".to_vec(),
+                process_name: None,
+            }),
+        ]);
+
+        let buffer = SharedBuffer::default();
+        let stats = capture_debug_output(
+            &mut source,
+            CaptureTarget::All,
+            &mut [buffer.sink(OutputFormat::Text, false)],
+            &CaptureOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            buffer.contents(),
+            concat!(
+                "[2026-06-01 09:08:07.006] [1 ?] \u{FFFD}\u{FFFD}\n",
+                "[2026-06-01 09:08:07.006] [1 ?] text\n",
+            )
+        );
+        assert_eq!(stats.replaced_messages, 1);
+    }
+
+    #[test]
+    fn skips_invalid_utf8_with_strict_encoding() {
+        let mut source = ScriptedSource::new(vec![SourceEvent::Message(DebugMessage {
+            pid: 1,
+            timestamp: test_timestamp(),
+            bytes: vec![b'a', 0xff],
+            process_name: None,
+        })]);
+        let options = CaptureOptions {
+            encoding: Encoding::Utf8,
+            ..CaptureOptions::default()
+        };
+
+        assert_eq!(capture_with(&mut source, CaptureTarget::All, &options), "");
+    }
+
+    #[test]
+    fn decodes_messages_with_code_page() {
+        let mut source = ScriptedSource::new(vec![SourceEvent::Message(DebugMessage {
+            pid: 1,
+            timestamp: test_timestamp(),
+            bytes: b"caf\xe9 \x80".to_vec(),
+            process_name: None,
+        })]);
+        let options = CaptureOptions {
+            encoding: Encoding::CodePage(CodePage::Cp1252),
+            ..CaptureOptions::default()
+        };
+
+        assert_eq!(
+            capture_with(&mut source, CaptureTarget::All, &options),
+            "[2026-06-01 09:08:07.006] [1 ?] café €\n"
+        );
+    }
+
+    #[test]
+    fn splits_multi_line_messages_into_prefixed_lines() {
+        let mut source = ScriptedSource::messages(&[(1, "first\nsecond\r\n")]);
+        let options = CaptureOptions {
+            split_lines: true,
+            ..CaptureOptions::default()
+        };
+
+        assert_eq!(
+            capture_with(&mut source, CaptureTarget::All, &options),
+            concat!(
+                "[2026-06-01 09:08:07.006] [1 ?] first\n",
+                "[2026-06-01 09:08:07.006] [1 ?] | second\n",
+            )
+        );
+    }
+
+    #[test]
+    fn highlights_written_lines() {
+        let mut source = ScriptedSource::messages(&[(1, "warn here")]);
+        let words = vec!["warn".to_string()];
+
+        assert_eq!(
+            capture(&mut source, CaptureTarget::All, &words),
+            "[2026-06-01 09:08:07.006] [1 ?] \x1b[34mwarn\x1b[39m here\n"
+        );
+    }
+
+    #[test]
+    fn writes_json_lines_without_highlighting() {
+        let mut source = ScriptedSource::messages(&[(1, "warn here\r\n")]);
+        let options = CaptureOptions {
+            highlight_words: vec!["warn".to_string()],
+            ..CaptureOptions::default()
+        };
+
+        assert_eq!(
+            capture_as(
+                &mut source,
+                CaptureTarget::All,
+                OutputFormat::Jsonl,
+                &options
+            ),
+            "{\"timestamp\":\"2026-06-01T09:08:07.006\",\"pid\":1,\
+             \"process_name\":null,\"message\":\"warn here\"}\n"
+        );
+    }
+
+    #[test]
+    fn writes_each_sink_in_its_own_format() {
+        let mut source = ScriptedSource::messages(&[(1, "warn here")]);
+        let options = CaptureOptions {
+            highlight_words: vec!["warn".to_string()],
+            ..CaptureOptions::default()
+        };
+        let console = SharedBuffer::default();
+        let file = SharedBuffer::default();
+        let jsonl = SharedBuffer::default();
+        let mut sinks = [
+            console.sink(OutputFormat::Text, true),
+            file.sink(OutputFormat::Text, false),
+            jsonl.sink(OutputFormat::Jsonl, false),
+        ];
+
+        capture_debug_output(&mut source, CaptureTarget::All, &mut sinks, &options).unwrap();
+
+        assert_eq!(
+            console.contents(),
+            "[2026-06-01 09:08:07.006] [1 ?] \x1b[34mwarn\x1b[39m here\n"
+        );
+        assert_eq!(
+            file.contents(),
+            "[2026-06-01 09:08:07.006] [1 ?] warn here\n"
+        );
+        assert!(jsonl.contents().ends_with("\"message\":\"warn here\"}\n"));
+    }
+
+    #[test]
+    fn writes_process_events_without_filtering() {
+        let mut source = ScriptedSource::new(vec![
+            SourceEvent::Process(ProcessEvent {
+                kind: ProcessEventKind::Started,
+                pid: 2,
+                process_name: Some("app.exe".to_string()),
+                timestamp: test_timestamp(),
+            }),
+            SourceEvent::Message(DebugMessage {
+                pid: 2,
+                timestamp: test_timestamp(),
+                bytes: b"noise".to_vec(),
+                process_name: Some("app.exe".to_string()),
+            }),
+        ]);
+        let options = CaptureOptions {
+            filter: MessageFilter::new(&FilterRules {
+                include_texts: vec!["error".to_string()],
+                ..FilterRules::default()
+            })
+            .unwrap(),
+            ..CaptureOptions::default()
+        };
+        let target = CaptureTarget::StaticPids(HashSet::from([1]));
+
+        assert_eq!(
+            capture_with(&mut source, target, &options),
+            "[2026-06-01 09:08:07.006] [2 app.exe] *** process started ***\n"
+        );
+    }
+
+    #[test]
+    fn numbers_written_messages_in_template() {
+        let mut source = ScriptedSource::new(
+            [(1, 0), (2, 0), (1, 250)]
+                .into_iter()
+                .map(|(pid, millisecond)| {
+                    let mut timestamp = test_timestamp();
+                    timestamp.millisecond = millisecond;
+                    SourceEvent::Message(DebugMessage {
+                        pid,
+                        timestamp,
+                        bytes: b"text".to_vec(),
+                        process_name: None,
+                    })
+                })
+                .collect(),
+        );
+        let options = CaptureOptions {
+            template: "{seq} {delta} {msg}".parse().unwrap(),
+            ..CaptureOptions::default()
+        };
+        let target = CaptureTarget::StaticPids(HashSet::from([1]));
+
+        assert_eq!(
+            capture_with(&mut source, target, &options),
+            "1 +0.000s text\n2 +0.250s text\n"
+        );
+    }
+
+    #[test]
+    fn writes_only_messages_matching_include_rules() {
+        let mut source =
+            ScriptedSource::messages(&[(1, "request timeout"), (1, "ok"), (2, "Error 5")]);
+        let options = CaptureOptions {
+            filter: MessageFilter::new(&FilterRules {
+                include_texts: vec!["TIMEOUT".to_string()],
+                include_regexes: vec!["^error".to_string()],
+                ..FilterRules::default()
+            })
+            .unwrap(),
+            ..CaptureOptions::default()
+        };
+
+        assert_eq!(
+            capture_with(&mut source, CaptureTarget::All, &options),
+            "[2026-06-01 09:08:07.006] [1 ?] request timeout\n\
+             [2026-06-01 09:08:07.006] [2 ?] Error 5\n"
+        );
+    }
+
+    #[test]
+    fn drops_messages_matching_exclude_rules() {
+        let mut source = ScriptedSource::messages(&[(1, "heartbeat"), (1, "failed"), (2, "noise")]);
+        let options = CaptureOptions {
+            filter: MessageFilter::new(&FilterRules {
+                exclude_texts: vec!["Heartbeat".to_string()],
+                ..FilterRules::default()
+            })
+            .unwrap(),
+            ..CaptureOptions::default()
+        };
+        let target = CaptureTarget::StaticPids(HashSet::from([1]));
+
+        assert_eq!(
+            capture_with(&mut source, target, &options),
+            "[2026-06-01 09:08:07.006] [1 ?] failed\n"
+        );
+    }
+
+    #[test]
+    fn writes_queued_messages_in_background() {
+        let texts: Vec<String> = (0..50).map(|n| format!("message {}", n)).collect();
+        let messages: Vec<(u32, &str)> = texts.iter().map(|text| (1, text.as_str())).collect();
+        let buffer = SharedBuffer::default();
+        let stats = capture_in_background(
+            &mut ScriptedSource::messages(&messages),
+            CaptureTarget::All,
+            &mut [buffer.sink(OutputFormat::Text, false)],
+            &CaptureOptions::default(),
+            2,
+            OverflowPolicy::Block,
+        )
+        .unwrap();
+
+        let lines: Vec<String> = buffer.contents().lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 50);
+        assert!(lines[49].ends_with("] message 49"));
+        assert_eq!(stats.dropped_messages, 0);
+    }
+
+    /// Output that counts how often it was flushed.
+    #[derive(Clone, Default)]
+    struct FlushCounter(Arc<Mutex<usize>>);
+
+    impl Write for FlushCounter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.0.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flushes_according_to_policy() {
+        let flushes = |flush| {
+            let counter = FlushCounter::default();
+            let mut sinks = [Sink {
+                output: SinkOutput::Stream(Box::new(counter.clone())),
+                format: OutputFormat::Text,
+                highlight: false,
+            }];
+            let options = CaptureOptions {
+                flush,
+                ..CaptureOptions::default()
+            };
+            let mut source = ScriptedSource::messages(&[(1, "a"), (1, "b"), (1, "c")]);
+            capture_debug_output(&mut source, CaptureTarget::All, &mut sinks, &options).unwrap();
+            *counter.0.lock().unwrap()
+        };
+
+        assert_eq!(flushes(FlushPolicy::Every), 4);
+        assert_eq!(flushes(FlushPolicy::Interval(Duration::from_secs(3600))), 1);
+        assert_eq!(flushes(FlushPolicy::OnExit), 1);
+    }
+
+    #[test]
+    fn parses_flush_policies() {
+        assert_eq!("every".parse(), Ok(FlushPolicy::Every));
+        assert_eq!(
+            "interval=500".parse(),
+            Ok(FlushPolicy::Interval(Duration::from_millis(500)))
+        );
+        assert_eq!("on-exit".parse(), Ok(FlushPolicy::OnExit));
+        assert!("interval=soon".parse::<FlushPolicy>().is_err());
+    }
+
+    struct FailingOutput;
+
+    impl Write for FailingOutput {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_reading_when_background_writer_fails() {
+        let messages = vec![(1, "message"); 100];
+        let mut sinks = [Sink {
+            output: SinkOutput::Stream(Box::new(FailingOutput)),
+            format: OutputFormat::Text,
+            highlight: false,
+        }];
+        let result = capture_in_background(
+            &mut ScriptedSource::messages(&messages),
+            CaptureTarget::All,
+            &mut sinks,
+            &CaptureOptions::default(),
+            1,
+            OverflowPolicy::Block,
+        );
+
+        assert!(matches!(result, Err(CaptureError::Io(_))));
+    }
+
+    #[test]
+    fn flushes_other_sinks_after_a_failure() {
+        let buffer = SharedBuffer::default();
+        let mut sinks = [
+            Sink {
+                output: SinkOutput::Stream(Box::new(BufWriter::new(buffer.clone()))),
+                format: OutputFormat::Text,
+                highlight: false,
+            },
+            Sink {
+                output: SinkOutput::Stream(Box::new(FailingOutput)),
+                format: OutputFormat::Text,
+                highlight: false,
+            },
+        ];
+        let options = CaptureOptions {
+            flush: FlushPolicy::OnExit,
+            ..CaptureOptions::default()
+        };
+        let mut source = ScriptedSource::messages(&[(1, "kept")]);
+        let result = capture_debug_output(&mut source, CaptureTarget::All, &mut sinks, &options);
+        assert!(result.is_err());
+        assert_eq!(buffer.contents(), "");
+
+        flush_remaining(&mut sinks);
+
+        assert_eq!(buffer.contents(), "[2026-06-01 09:08:07.006] [1 ?] kept\n");
+    }
+}
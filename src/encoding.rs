@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::winproc::{ansi_code_page, oem_code_page};
+
+/// Single-byte code pages with a built-in decoding table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodePage {
+    Cp1252,
+    Latin1,
+    Cp1251,
+    Cp437,
+    Cp850,
+    Cp866,
+}
+
+impl CodePage {
+    const ALL: [CodePage; 6] = [
+        CodePage::Cp1252,
+        CodePage::Latin1,
+        CodePage::Cp1251,
+        CodePage::Cp437,
+        CodePage::Cp850,
+        CodePage::Cp866,
+    ];
+
+    /// Windows code page identifier.
+    fn number(self) -> u32 {
+        match self {
+            CodePage::Cp1252 => 1252,
+            CodePage::Latin1 => 28591,
+            CodePage::Cp1251 => 1251,
+            CodePage::Cp437 => 437,
+            CodePage::Cp850 => 850,
+            CodePage::Cp866 => 866,
+        }
+    }
+
+    fn from_number(number: u32) -> Option<Self> {
+        CodePage::ALL
+            .into_iter()
+            .find(|code_page| code_page.number() == number)
+    }
+
+    /// Characters for bytes 0x80-0xFF; bytes below are ASCII.
+    fn high_half(self) -> Option<&'static [u16; 128]> {
+        match self {
+            CodePage::Cp1252 => Some(&CP1252),
+            CodePage::Latin1 => None,
+            CodePage::Cp1251 => Some(&CP1251),
+            CodePage::Cp437 => Some(&CP437),
+            CodePage::Cp850 => Some(&CP850),
+            CodePage::Cp866 => Some(&CP866),
+        }
+    }
+
+    /// Decodes `bytes`, returning whether an undefined byte was replaced.
+    fn decode(self, bytes: &[u8]) -> (String, bool) {
+        let table = self.high_half();
+        let mut replaced = false;
+        let text = bytes
+            .iter()
+            .map(|&byte| match (byte, table) {
+                (0..0x80, _) | (_, None) => char::from(byte),
+                (_, Some(table)) => {
+                    let ch = char::from_u32(table[usize::from(byte - 0x80)].into())
+                        .unwrap_or(char::REPLACEMENT_CHARACTER);
+                    replaced |= ch == char::REPLACEMENT_CHARACTER;
+                    ch
+                }
+            })
+            .collect();
+        (text, replaced)
+    }
+}
+
+impl fmt::Display for CodePage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodePage::Latin1 => write!(f, "latin1"),
+            code_page => write!(f, "cp{}", code_page.number()),
+        }
+    }
+}
+
+/// How message bytes are turned into text, see `--encoding`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Strict UTF-8; messages that are not valid UTF-8 are dropped.
+    Utf8,
+    /// UTF-8 with invalid sequences replaced by U+FFFD.
+    #[default]
+    Lossy,
+    CodePage(CodePage),
+}
+
+/// Message text and whether it needed replacement characters.
+pub struct Decoded<'a> {
+    pub text: Cow<'a, str>,
+    pub replaced: bool,
+}
+
+impl Encoding {
+    /// Decodes `bytes`; `None` only for invalid input with strict UTF-8.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Option<Decoded<'a>> {
+        match self {
+            Encoding::Utf8 => std::str::from_utf8(bytes).ok().map(|text| Decoded {
+                text: Cow::Borrowed(text),
+                replaced: false,
+            }),
+            Encoding::Lossy => {
+                let text = String::from_utf8_lossy(bytes);
+                let replaced = matches!(text, Cow::Owned(_));
+                Some(Decoded { text, replaced })
+            }
+            Encoding::CodePage(code_page) => {
+                let (text, replaced) = code_page.decode(bytes);
+                Some(Decoded {
+                    text: Cow::Owned(text),
+                    replaced,
+                })
+            }
+        }
+    }
+}
+
+/// Resolves the system's `oem` or `ansi` code page to a built-in table.
+fn system_code_page(name: &str, number: u32) -> Result<Encoding, String> {
+    CodePage::from_number(number)
+        .map(Encoding::CodePage)
+        .ok_or_else(|| {
+            format!(
+                "The {} code page {} has no built-in table; use one of {} instead.",
+                name,
+                number,
+                built_in_code_pages()
+            )
+        })
+}
+
+fn built_in_code_pages() -> String {
+    CodePage::ALL
+        .map(|code_page| code_page.to_string())
+        .join(", ")
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let code_page = match value.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => return Ok(Encoding::Utf8),
+            "lossy" => return Ok(Encoding::Lossy),
+            "oem" => return system_code_page("OEM", oem_code_page()),
+            "ansi" => return system_code_page("ANSI", ansi_code_page()),
+            "cp1252" | "windows-1252" => CodePage::Cp1252,
+            "latin1" | "iso-8859-1" => CodePage::Latin1,
+            "cp1251" | "windows-1251" => CodePage::Cp1251,
+            "cp437" => CodePage::Cp437,
+            "cp850" => CodePage::Cp850,
+            "cp866" => CodePage::Cp866,
+            _ => {
+                return Err(format!(
+                    "Unknown encoding '{}'. Expected utf8, lossy, oem, ansi or one of {}.",
+                    value,
+                    built_in_code_pages()
+                ));
+            }
+        };
+        Ok(Encoding::CodePage(code_page))
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "utf8"),
+            Encoding::Lossy => write!(f, "lossy UTF-8"),
+            Encoding::CodePage(code_page) => code_page.fmt(f),
+        }
+    }
+}
+
+const CP1252: [u16; 128] = [
+    0x20AC, 0xFFFD, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0xFFFD, 0x017D, 0xFFFD, 0xFFFD, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0xFFFD, 0x017E, 0x0178, 0x00A0, 0x00A1, 0x00A2, 0x00A3,
+    0x00A4, 0x00A5, 0x00A6, 0x00A7, 0x00A8, 0x00A9, 0x00AA, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x00AF,
+    0x00B0, 0x00B1, 0x00B2, 0x00B3, 0x00B4, 0x00B5, 0x00B6, 0x00B7, 0x00B8, 0x00B9, 0x00BA, 0x00BB,
+    0x00BC, 0x00BD, 0x00BE, 0x00BF, 0x00C0, 0x00C1, 0x00C2, 0x00C3, 0x00C4, 0x00C5, 0x00C6, 0x00C7,
+    0x00C8, 0x00C9, 0x00CA, 0x00CB, 0x00CC, 0x00CD, 0x00CE, 0x00CF, 0x00D0, 0x00D1, 0x00D2, 0x00D3,
+    0x00D4, 0x00D5, 0x00D6, 0x00D7, 0x00D8, 0x00D9, 0x00DA, 0x00DB, 0x00DC, 0x00DD, 0x00DE, 0x00DF,
+    0x00E0, 0x00E1, 0x00E2, 0x00E3, 0x00E4, 0x00E5, 0x00E6, 0x00E7, 0x00E8, 0x00E9, 0x00EA, 0x00EB,
+    0x00EC, 0x00ED, 0x00EE, 0x00EF, 0x00F0, 0x00F1, 0x00F2, 0x00F3, 0x00F4, 0x00F5, 0x00F6, 0x00F7,
+    0x00F8, 0x00F9, 0x00FA, 0x00FB, 0x00FC, 0x00FD, 0x00FE, 0x00FF,
+];
+const CP1251: [u16; 128] = [
+    0x0402, 0x0403, 0x201A, 0x0453, 0x201E, 0x2026, 0x2020, 0x2021, 0x20AC, 0x2030, 0x0409, 0x2039,
+    0x040A, 0x040C, 0x040B, 0x040F, 0x0452, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0xFFFD, 0x2122, 0x0459, 0x203A, 0x045A, 0x045C, 0x045B, 0x045F, 0x00A0, 0x040E, 0x045E, 0x0408,
+    0x00A4, 0x0490, 0x00A6, 0x00A7, 0x0401, 0x00A9, 0x0404, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x0407,
+    0x00B0, 0x00B1, 0x0406, 0x0456, 0x0491, 0x00B5, 0x00B6, 0x00B7, 0x0451, 0x2116, 0x0454, 0x00BB,
+    0x0458, 0x0405, 0x0455, 0x0457, 0x0410, 0x0411, 0x0412, 0x0413, 0x0414, 0x0415, 0x0416, 0x0417,
+    0x0418, 0x0419, 0x041A, 0x041B, 0x041C, 0x041D, 0x041E, 0x041F, 0x0420, 0x0421, 0x0422, 0x0423,
+    0x0424, 0x0425, 0x0426, 0x0427, 0x0428, 0x0429, 0x042A, 0x042B, 0x042C, 0x042D, 0x042E, 0x042F,
+    0x0430, 0x0431, 0x0432, 0x0433, 0x0434, 0x0435, 0x0436, 0x0437, 0x0438, 0x0439, 0x043A, 0x043B,
+    0x043C, 0x043D, 0x043E, 0x043F, 0x0440, 0x0441, 0x0442, 0x0443, 0x0444, 0x0445, 0x0446, 0x0447,
+    0x0448, 0x0449, 0x044A, 0x044B, 0x044C, 0x044D, 0x044E, 0x044F,
+];
+const CP437: [u16; 128] = [
+    0x00C7, 0x00FC, 0x00E9, 0x00E2, 0x00E4, 0x00E0, 0x00E5, 0x00E7, 0x00EA, 0x00EB, 0x00E8, 0x00EF,
+    0x00EE, 0x00EC, 0x00C4, 0x00C5, 0x00C9, 0x00E6, 0x00C6, 0x00F4, 0x00F6, 0x00F2, 0x00FB, 0x00F9,
+    0x00FF, 0x00D6, 0x00DC, 0x00A2, 0x00A3, 0x00A5, 0x20A7, 0x0192, 0x00E1, 0x00ED, 0x00F3, 0x00FA,
+    0x00F1, 0x00D1, 0x00AA, 0x00BA, 0x00BF, 0x2310, 0x00AC, 0x00BD, 0x00BC, 0x00A1, 0x00AB, 0x00BB,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255D, 0x255C, 0x255B, 0x2510, 0x2514, 0x2534, 0x252C, 0x251C, 0x2500, 0x253C, 0x255E, 0x255F,
+    0x255A, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256C, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256B, 0x256A, 0x2518, 0x250C, 0x2588, 0x2584, 0x258C, 0x2590, 0x2580,
+    0x03B1, 0x00DF, 0x0393, 0x03C0, 0x03A3, 0x03C3, 0x00B5, 0x03C4, 0x03A6, 0x0398, 0x03A9, 0x03B4,
+    0x221E, 0x03C6, 0x03B5, 0x2229, 0x2261, 0x00B1, 0x2265, 0x2264, 0x2320, 0x2321, 0x00F7, 0x2248,
+    0x00B0, 0x2219, 0x00B7, 0x221A, 0x207F, 0x00B2, 0x25A0, 0x00A0,
+];
+const CP850: [u16; 128] = [
+    0x00C7, 0x00FC, 0x00E9, 0x00E2, 0x00E4, 0x00E0, 0x00E5, 0x00E7, 0x00EA, 0x00EB, 0x00E8, 0x00EF,
+    0x00EE, 0x00EC, 0x00C4, 0x00C5, 0x00C9, 0x00E6, 0x00C6, 0x00F4, 0x00F6, 0x00F2, 0x00FB, 0x00F9,
+    0x00FF, 0x00D6, 0x00DC, 0x00F8, 0x00A3, 0x00D8, 0x00D7, 0x0192, 0x00E1, 0x00ED, 0x00F3, 0x00FA,
+    0x00F1, 0x00D1, 0x00AA, 0x00BA, 0x00BF, 0x00AE, 0x00AC, 0x00BD, 0x00BC, 0x00A1, 0x00AB, 0x00BB,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x00C1, 0x00C2, 0x00C0, 0x00A9, 0x2563, 0x2551, 0x2557,
+    0x255D, 0x00A2, 0x00A5, 0x2510, 0x2514, 0x2534, 0x252C, 0x251C, 0x2500, 0x253C, 0x00E3, 0x00C3,
+    0x255A, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256C, 0x00A4, 0x00F0, 0x00D0, 0x00CA, 0x00CB,
+    0x00C8, 0x0131, 0x00CD, 0x00CE, 0x00CF, 0x2518, 0x250C, 0x2588, 0x2584, 0x00A6, 0x00CC, 0x2580,
+    0x00D3, 0x00DF, 0x00D4, 0x00D2, 0x00F5, 0x00D5, 0x00B5, 0x00FE, 0x00DE, 0x00DA, 0x00DB, 0x00D9,
+    0x00FD, 0x00DD, 0x00AF, 0x00B4, 0x00AD, 0x00B1, 0x2017, 0x00BE, 0x00B6, 0x00A7, 0x00F7, 0x00B8,
+    0x00B0, 0x00A8, 0x00B7, 0x00B9, 0x00B3, 0x00B2, 0x25A0, 0x00A0,
+];
+const CP866: [u16; 128] = [
+    0x0410, 0x0411, 0x0412, 0x0413, 0x0414, 0x0415, 0x0416, 0x0417, 0x0418, 0x0419, 0x041A, 0x041B,
+    0x041C, 0x041D, 0x041E, 0x041F, 0x0420, 0x0421, 0x0422, 0x0423, 0x0424, 0x0425, 0x0426, 0x0427,
+    0x0428, 0x0429, 0x042A, 0x042B, 0x042C, 0x042D, 0x042E, 0x042F, 0x0430, 0x0431, 0x0432, 0x0433,
+    0x0434, 0x0435, 0x0436, 0x0437, 0x0438, 0x0439, 0x043A, 0x043B, 0x043C, 0x043D, 0x043E, 0x043F,
+    0x2591, 0x2592, 0x2593, 0x2502, 0x2524, 0x2561, 0x2562, 0x2556, 0x2555, 0x2563, 0x2551, 0x2557,
+    0x255D, 0x255C, 0x255B, 0x2510, 0x2514, 0x2534, 0x252C, 0x251C, 0x2500, 0x253C, 0x255E, 0x255F,
+    0x255A, 0x2554, 0x2569, 0x2566, 0x2560, 0x2550, 0x256C, 0x2567, 0x2568, 0x2564, 0x2565, 0x2559,
+    0x2558, 0x2552, 0x2553, 0x256B, 0x256A, 0x2518, 0x250C, 0x2588, 0x2584, 0x258C, 0x2590, 0x2580,
+    0x0440, 0x0441, 0x0442, 0x0443, 0x0444, 0x0445, 0x0446, 0x0447, 0x0448, 0x0449, 0x044A, 0x044B,
+    0x044C, 0x044D, 0x044E, 0x044F, 0x0401, 0x0451, 0x0404, 0x0454, 0x0407, 0x0457, 0x040E, 0x045E,
+    0x00B0, 0x2219, 0x00B7, 0x221A, 0x2116, 0x00A4, 0x25A0, 0x00A0,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{CodePage, Encoding, system_code_page};
+
+    fn decode(encoding: Encoding, bytes: &[u8]) -> Option<(String, bool)> {
+        encoding
+            .decode(bytes)
+            .map(|decoded| (decoded.text.into_owned(), decoded.replaced))
+    }
+
+    #[test]
+    fn decodes_utf8_strictly_or_lossily() {
+        assert_eq!(
+            decode(Encoding::Utf8, "grüß".as_bytes()),
+            Some(("grüß".to_string(), false))
+        );
+        assert_eq!(decode(Encoding::Utf8, b"bad \xff"), None);
+        assert_eq!(
+            decode(Encoding::Lossy, b"bad \xff"),
+            Some(("bad \u{FFFD}".to_string(), true))
+        );
+        assert_eq!(
+            decode(Encoding::Lossy, b"fine"),
+            Some(("fine".to_string(), false))
+        );
+    }
+
+    #[test]
+    fn decodes_single_byte_code_pages() {
+        let cases: [(CodePage, &[u8], &str); 5] = [
+            (
+                CodePage::Cp1252,
+                b"\x80 caf\xe9 \x93q\x94",
+                "€ café \u{201C}q\u{201D}",
+            ),
+            (CodePage::Latin1, b"caf\xe9 \x80", "café \u{80}"),
+            (CodePage::Cp1251, b"\xcf\xf0\xe8\xe2\xe5\xf2", "Привет"),
+            (CodePage::Cp437, b"\x82t\x82 \xc9\xcd\xbb", "été ╔═╗"),
+            (CodePage::Cp866, b"\x8f\xe0\xa8\xa2\xa5\xe2", "Привет"),
+        ];
+        for (code_page, bytes, expected) in cases {
+            assert_eq!(
+                decode(Encoding::CodePage(code_page), bytes),
+                Some((expected.to_string(), false)),
+                "{}",
+                code_page
+            );
+        }
+        assert_eq!(
+            decode(Encoding::CodePage(CodePage::Cp850), b"\x9b\xd5"),
+            Some(("øı".to_string(), false))
+        );
+    }
+
+    #[test]
+    fn replaces_undefined_bytes() {
+        assert_eq!(
+            decode(Encoding::CodePage(CodePage::Cp1252), b"a\x81b"),
+            Some(("a\u{FFFD}b".to_string(), true))
+        );
+    }
+
+    #[test]
+    fn parses_encoding_names() {
+        assert_eq!("UTF-8".parse(), Ok(Encoding::Utf8));
+        assert_eq!("lossy".parse(), Ok(Encoding::Lossy));
+        assert_eq!(
+            "windows-1252".parse(),
+            Ok(Encoding::CodePage(CodePage::Cp1252))
+        );
+        assert_eq!("cp866".parse(), Ok(Encoding::CodePage(CodePage::Cp866)));
+        assert!("ebcdic".parse::<Encoding>().is_err());
+        assert_eq!(Encoding::CodePage(CodePage::Cp437).to_string(), "cp437");
+    }
+
+    #[test]
+    fn resolves_system_code_page_numbers() {
+        assert_eq!(
+            system_code_page("OEM", 850),
+            Ok(Encoding::CodePage(CodePage::Cp850))
+        );
+        let err = system_code_page("ANSI", 932).unwrap_err();
+        assert!(err.contains("ANSI code page 932 has no built-in table"));
+    }
+}
@@ -0,0 +1,260 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::filterexpr::{FilterContext, FilterExpr};
+use crate::source::DebugMessage;
+
+#[derive(Debug)]
+enum MatchRule {
+    /// Substring, stored lowercased when matching case-insensitively.
+    Text(String),
+    Regex(Regex),
+}
+
+impl MatchRule {
+    fn matches(&self, text: &str, lowercase_text: Option<&str>) -> bool {
+        match self {
+            MatchRule::Text(needle) => lowercase_text.unwrap_or(text).contains(needle.as_str()),
+            MatchRule::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+pub fn build_regex(pattern: &str, case_sensitive: bool) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid regex '{}': {}", pattern, e))
+}
+
+fn build_rules(
+    texts: &[String],
+    regexes: &[String],
+    case_sensitive: bool,
+) -> Result<Vec<MatchRule>, String> {
+    let mut rules: Vec<MatchRule> = texts
+        .iter()
+        .map(|text| {
+            MatchRule::Text(if case_sensitive {
+                text.clone()
+            } else {
+                text.to_ascii_lowercase()
+            })
+        })
+        .collect();
+    for pattern in regexes {
+        rules.push(MatchRule::Regex(build_regex(pattern, case_sensitive)?));
+    }
+
+    Ok(rules)
+}
+
+/// Raw `--include*` / `--exclude*` values collected from the command line.
+#[derive(Debug, Default)]
+pub struct FilterRules {
+    pub include_texts: Vec<String>,
+    pub include_regexes: Vec<String>,
+    pub exclude_texts: Vec<String>,
+    pub exclude_regexes: Vec<String>,
+    /// `--filter` expression source.
+    pub expression: Option<String>,
+    /// Substring and regex matching ignore case unless set, like `--highlight`.
+    pub case_sensitive: bool,
+}
+
+/// Message-content rules applied after PID matching.
+#[derive(Debug, Default)]
+pub struct MessageFilter {
+    include: Vec<MatchRule>,
+    exclude: Vec<MatchRule>,
+    expression: Option<FilterExpr>,
+    case_sensitive: bool,
+}
+
+impl MessageFilter {
+    pub fn new(rules: &FilterRules) -> Result<Self, String> {
+        let expression = rules
+            .expression
+            .as_deref()
+            .map(|expression| FilterExpr::parse(expression, rules.case_sensitive))
+            .transpose()
+            .map_err(|e| format!("Invalid --filter expression at {}", e))?;
+
+        Ok(MessageFilter {
+            include: build_rules(
+                &rules.include_texts,
+                &rules.include_regexes,
+                rules.case_sensitive,
+            )?,
+            exclude: build_rules(
+                &rules.exclude_texts,
+                &rules.exclude_regexes,
+                rules.case_sensitive,
+            )?,
+            expression,
+            case_sensitive: rules.case_sensitive,
+        })
+    }
+
+    /// Checks the include/exclude rules and the `--filter` expression.
+    pub fn accepts(&self, message: &DebugMessage, text: &str) -> bool {
+        self.matches(text)
+            && self.expression.as_ref().is_none_or(|expression| {
+                expression.evaluate(&FilterContext {
+                    pid: message.pid,
+                    name: message.process_name.as_deref(),
+                    msg: text,
+                })
+            })
+    }
+
+    /// A message passes when it matches any include rule (or none are set) and no exclude rule.
+    pub fn matches(&self, text: &str) -> bool {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+
+        let lowercase_text = (!self.case_sensitive).then(|| text.to_ascii_lowercase());
+        let lowercase_text = lowercase_text.as_deref();
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|rule| rule.matches(text, lowercase_text));
+
+        included
+            && !self
+                .exclude
+                .iter()
+                .any(|rule| rule.matches(text, lowercase_text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterRules, MessageFilter};
+    use crate::source::{DebugMessage, test_timestamp};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn filter(texts: &[&str], regexes: &[&str], case_sensitive: bool) -> MessageFilter {
+        MessageFilter::new(&FilterRules {
+            include_texts: strings(texts),
+            include_regexes: strings(regexes),
+            case_sensitive,
+            ..FilterRules::default()
+        })
+        .unwrap()
+    }
+
+    fn exclude_filter(texts: &[&str], regexes: &[&str]) -> MessageFilter {
+        MessageFilter::new(&FilterRules {
+            exclude_texts: strings(texts),
+            exclude_regexes: strings(regexes),
+            ..FilterRules::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_everything_without_rules() {
+        assert!(filter(&[], &[], false).matches("anything"));
+    }
+
+    #[test]
+    fn includes_substrings_case_insensitively() {
+        let filter = filter(&["Timeout", "error"], &[], false);
+
+        assert!(filter.matches("connection TIMEOUT"));
+        assert!(filter.matches("Error 5"));
+        assert!(!filter.matches("all good"));
+    }
+
+    #[test]
+    fn includes_regex_matches() {
+        let filter = filter(&[], &[r"^\[net\] retry \d+$"], false);
+
+        assert!(filter.matches("[NET] retry 3"));
+        assert!(!filter.matches("[net] retry now"));
+    }
+
+    #[test]
+    fn respects_case_sensitive_matching() {
+        let filter = filter(&["Error"], &["^Warn"], true);
+
+        assert!(filter.matches("Error 5"));
+        assert!(!filter.matches("error 5"));
+        assert!(filter.matches("Warn: disk"));
+        assert!(!filter.matches("warn: disk"));
+    }
+
+    #[test]
+    fn excludes_matching_messages() {
+        let filter = exclude_filter(&["HEARTBEAT"], &[r"^d3d11: .* leaked$"]);
+
+        assert!(!filter.matches("heartbeat ok"));
+        assert!(!filter.matches("D3D11: buffer leaked"));
+        assert!(filter.matches("request failed"));
+    }
+
+    #[test]
+    fn exclude_rules_win_over_include_rules() {
+        let filter = MessageFilter::new(&FilterRules {
+            include_texts: strings(&["net"]),
+            exclude_texts: strings(&["keepalive"]),
+            ..FilterRules::default()
+        })
+        .unwrap();
+
+        assert!(filter.matches("net: connected"));
+        assert!(!filter.matches("net: keepalive"));
+        assert!(!filter.matches("disk: full"));
+    }
+
+    #[test]
+    fn accepts_messages_matching_expression_and_rules() {
+        let filter = MessageFilter::new(&FilterRules {
+            exclude_texts: strings(&["heartbeat"]),
+            expression: Some("pid == 7 || name == \"svc.exe\"".to_string()),
+            ..FilterRules::default()
+        })
+        .unwrap();
+        let message = |pid, name: Option<&str>| DebugMessage {
+            pid,
+            timestamp: test_timestamp(),
+            bytes: Vec::new(),
+            process_name: name.map(str::to_string),
+        };
+
+        assert!(filter.accepts(&message(7, None), "started"));
+        assert!(filter.accepts(&message(8, Some("svc.exe")), "started"));
+        assert!(!filter.accepts(&message(8, Some("app.exe")), "started"));
+        assert!(!filter.accepts(&message(7, None), "heartbeat"));
+    }
+
+    #[test]
+    fn reports_invalid_expression() {
+        let err = MessageFilter::new(&FilterRules {
+            expression: Some("pid ==".to_string()),
+            ..FilterRules::default()
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            "Invalid --filter expression at column 7: pid must be compared to a number"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_regex() {
+        let err = MessageFilter::new(&FilterRules {
+            exclude_regexes: strings(&["("]),
+            ..FilterRules::default()
+        })
+        .unwrap_err();
+
+        assert!(err.contains("Invalid regex '('"));
+    }
+}
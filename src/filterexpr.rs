@@ -0,0 +1,565 @@
+// filterexpr.rs
+// Parser and evaluator for --filter expressions such as
+// `pid == 1234 && (msg ~ /timeout/i || name == "svc.exe") && !msg contains "heartbeat"`
+use std::str::FromStr;
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u32),
+    Str(String),
+    Regex { pattern: String, flags: String },
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Number(value) => format!("number {}", value),
+            Token::Str(_) => "a string".to_string(),
+            Token::Regex { .. } => "a regex".to_string(),
+            Token::Eq => "'=='".to_string(),
+            Token::Ne => "'!='".to_string(),
+            Token::Lt => "'<'".to_string(),
+            Token::Le => "'<='".to_string(),
+            Token::Gt => "'>'".to_string(),
+            Token::Ge => "'>='".to_string(),
+            Token::Match => "'~'".to_string(),
+            Token::NotMatch => "'!~'".to_string(),
+            Token::And => "'&&'".to_string(),
+            Token::Or => "'||'".to_string(),
+            Token::Not => "'!'".to_string(),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+        }
+    }
+}
+
+/// Token with its 1-based column in the expression.
+type Spanned = (Token, usize);
+
+fn error_at(column: usize, message: &str) -> String {
+    format!("column {}: {}", column, message)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Spanned>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        let column = input[..index].chars().count() + 1;
+        let mut next_is = |expected: char| chars.next_if(|&(_, ch)| ch == expected).is_some();
+        let token = match ch {
+            ch if ch.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '~' => Token::Match,
+            '=' if next_is('=') => Token::Eq,
+            '!' if next_is('=') => Token::Ne,
+            '!' if next_is('~') => Token::NotMatch,
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Le,
+            '<' => Token::Lt,
+            '>' if next_is('=') => Token::Ge,
+            '>' => Token::Gt,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 't')) => value.push('\t'),
+                            Some((_, ch)) => value.push(ch),
+                            None => return Err(error_at(column, "unterminated string")),
+                        },
+                        Some((_, ch)) => value.push(ch),
+                        None => return Err(error_at(column, "unterminated string")),
+                    }
+                }
+                Token::Str(value)
+            }
+            '/' => {
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '/')) => break,
+                        Some((_, '\\')) if chars.peek().is_some_and(|&(_, ch)| ch == '/') => {
+                            chars.next();
+                            pattern.push('/');
+                        }
+                        Some((_, ch)) => pattern.push(ch),
+                        None => return Err(error_at(column, "unterminated regex")),
+                    }
+                }
+                let mut flags = String::new();
+                while let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_ascii_alphabetic()) {
+                    flags.push(ch);
+                }
+                Token::Regex { pattern, flags }
+            }
+            ch if ch.is_ascii_digit() => {
+                let mut digits = ch.to_string();
+                while let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_ascii_digit()) {
+                    digits.push(ch);
+                }
+                let value = digits
+                    .parse()
+                    .map_err(|_| error_at(column, &format!("number {} is too large", digits)))?;
+                Token::Number(value)
+            }
+            ch if ch.is_ascii_alphabetic() || ch == '_' => {
+                let mut ident = ch.to_string();
+                while let Some((_, ch)) =
+                    chars.next_if(|&(_, ch)| ch.is_ascii_alphanumeric() || ch == '_')
+                {
+                    ident.push(ch);
+                }
+                Token::Ident(ident)
+            }
+            ch => return Err(error_at(column, &format!("unexpected character '{}'", ch))),
+        };
+        tokens.push((token, column));
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumberOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextField {
+    Name,
+    Msg,
+}
+
+#[derive(Debug)]
+enum TextOp {
+    Eq(String),
+    Ne(String),
+    Contains(String),
+    Match(Regex),
+    NotMatch(Regex),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Pid(NumberOp, u32),
+    Text(TextField, TextOp),
+}
+
+/// Values an expression is evaluated against.
+pub struct FilterContext<'a> {
+    pub pid: u32,
+    pub name: Option<&'a str>,
+    pub msg: &'a str,
+}
+
+fn text_equals(left: &str, right: &str, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        left == right
+    } else {
+        left.eq_ignore_ascii_case(right)
+    }
+}
+
+fn text_contains(text: &str, needle: &str, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        text.contains(needle)
+    } else {
+        text.to_ascii_lowercase()
+            .contains(&needle.to_ascii_lowercase())
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, context: &FilterContext, case_sensitive: bool) -> bool {
+        match self {
+            Expr::Or(left, right) => {
+                left.evaluate(context, case_sensitive) || right.evaluate(context, case_sensitive)
+            }
+            Expr::And(left, right) => {
+                left.evaluate(context, case_sensitive) && right.evaluate(context, case_sensitive)
+            }
+            Expr::Not(inner) => !inner.evaluate(context, case_sensitive),
+            Expr::Pid(op, value) => match op {
+                NumberOp::Eq => context.pid == *value,
+                NumberOp::Ne => context.pid != *value,
+                NumberOp::Lt => context.pid < *value,
+                NumberOp::Le => context.pid <= *value,
+                NumberOp::Gt => context.pid > *value,
+                NumberOp::Ge => context.pid >= *value,
+            },
+            Expr::Text(field, op) => {
+                let text = match field {
+                    TextField::Name => context.name,
+                    TextField::Msg => Some(context.msg),
+                };
+                // An unknown process name only satisfies negated comparisons.
+                let Some(text) = text else {
+                    return matches!(op, TextOp::Ne(_) | TextOp::NotMatch(_));
+                };
+                match op {
+                    TextOp::Eq(value) => text_equals(text, value, case_sensitive),
+                    TextOp::Ne(value) => !text_equals(text, value, case_sensitive),
+                    TextOp::Contains(value) => text_contains(text, value, case_sensitive),
+                    TextOp::Match(regex) => regex.is_match(text),
+                    TextOp::NotMatch(regex) => !regex.is_match(text),
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    position: usize,
+    end_column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end_column, |(_, column)| *column)
+    }
+
+    fn next(&mut self) -> Option<Spanned> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expected(&self, what: &str) -> String {
+        match self.peek() {
+            Some(token) => error_at(
+                self.column(),
+                &format!("expected {}, found {}", what, token.describe()),
+            ),
+            None => error_at(
+                self.column(),
+                &format!("expected {}, found end of expression", what),
+            ),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::LParen) => {
+                self.next();
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.expected("')'"));
+                }
+                self.next();
+                Ok(expr)
+            }
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let field = match self.peek() {
+            Some(Token::Ident(name)) if name == "pid" || name == "name" || name == "msg" => {
+                name.clone()
+            }
+            _ => return Err(self.expected("pid, name, msg, '!' or '('")),
+        };
+        self.next();
+
+        let op_column = self.column();
+        let Some((op, _)) = self.next() else {
+            return Err(error_at(
+                op_column,
+                &format!("expected an operator after '{}'", field),
+            ));
+        };
+        let value_column = self.column();
+        let value = self.next().map(|(token, _)| token);
+
+        if field == "pid" {
+            let op = match op {
+                Token::Eq => NumberOp::Eq,
+                Token::Ne => NumberOp::Ne,
+                Token::Lt => NumberOp::Lt,
+                Token::Le => NumberOp::Le,
+                Token::Gt => NumberOp::Gt,
+                Token::Ge => NumberOp::Ge,
+                other => {
+                    return Err(error_at(
+                        op_column,
+                        &format!(
+                            "pid supports ==, !=, <, <=, > and >=, found {}",
+                            other.describe()
+                        ),
+                    ));
+                }
+            };
+            return match value {
+                Some(Token::Number(value)) => Ok(Expr::Pid(op, value)),
+                _ => Err(error_at(value_column, "pid must be compared to a number")),
+            };
+        }
+
+        let field = if field == "name" {
+            TextField::Name
+        } else {
+            TextField::Msg
+        };
+        let string_value = |value: Option<Token>| match value {
+            Some(Token::Str(value)) => Ok(value),
+            _ => Err(error_at(value_column, "expected a quoted string")),
+        };
+        let regex_value =
+            |value: Option<Token>| compile_regex(value).map_err(|e| error_at(value_column, &e));
+        let op = match op {
+            Token::Eq => TextOp::Eq(string_value(value)?),
+            Token::Ne => TextOp::Ne(string_value(value)?),
+            Token::Ident(keyword) if keyword == "contains" => {
+                TextOp::Contains(string_value(value)?)
+            }
+            Token::Match => TextOp::Match(regex_value(value)?),
+            Token::NotMatch => TextOp::NotMatch(regex_value(value)?),
+            other => {
+                return Err(error_at(
+                    op_column,
+                    &format!(
+                        "name and msg support ==, !=, contains, ~ and !~, found {}",
+                        other.describe()
+                    ),
+                ));
+            }
+        };
+        Ok(Expr::Text(field, op))
+    }
+}
+
+fn compile_regex(token: Option<Token>) -> Result<Regex, String> {
+    let (pattern, flags) = match token {
+        Some(Token::Regex { pattern, flags }) => (pattern, flags),
+        Some(Token::Str(pattern)) => (pattern, String::new()),
+        _ => return Err("expected /regex/ or a quoted pattern".to_string()),
+    };
+
+    let mut builder = RegexBuilder::new(&pattern);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            flag => return Err(format!("unknown regex flag '{}'", flag)),
+        };
+    }
+    builder
+        .build()
+        .map_err(|e| format!("invalid regex /{}/: {}", pattern, e))
+}
+
+/// A parsed `--filter` expression.
+#[derive(Debug)]
+pub struct FilterExpr {
+    expr: Expr,
+    case_sensitive: bool,
+}
+
+impl FilterExpr {
+    /// `==`, `!=` and `contains` ignore ASCII case unless `case_sensitive`;
+    /// regexes are case-sensitive unless written with the `i` flag.
+    pub fn parse(input: &str, case_sensitive: bool) -> Result<Self, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end_column: input.chars().count() + 1,
+        };
+        let expr = parser.parse_or()?;
+        if parser.peek().is_some() {
+            return Err(parser.expected("'&&', '||' or end of expression"));
+        }
+
+        Ok(FilterExpr {
+            expr,
+            case_sensitive,
+        })
+    }
+
+    pub fn evaluate(&self, context: &FilterContext) -> bool {
+        self.expr.evaluate(context, self.case_sensitive)
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        FilterExpr::parse(value, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterContext, FilterExpr};
+
+    fn eval(expr: &str, pid: u32, name: Option<&str>, msg: &str) -> bool {
+        expr.parse::<FilterExpr>()
+            .unwrap()
+            .evaluate(&FilterContext { pid, name, msg })
+    }
+
+    fn parse_error(expr: &str) -> String {
+        expr.parse::<FilterExpr>().unwrap_err()
+    }
+
+    #[test]
+    fn evaluates_example_expression() {
+        let expr = r#"pid == 1234 && (msg ~ /timeout/i || name == "svc.exe") && !msg contains "heartbeat""#;
+
+        assert!(eval(expr, 1234, Some("app.exe"), "request TIMEOUT"));
+        assert!(eval(expr, 1234, Some("SVC.EXE"), "started"));
+        assert!(!eval(expr, 1234, Some("svc.exe"), "heartbeat sent"));
+        assert!(!eval(expr, 99, Some("svc.exe"), "started"));
+        assert!(!eval(expr, 1234, Some("app.exe"), "started"));
+    }
+
+    #[test]
+    fn binds_and_tighter_than_or() {
+        let expr = "pid == 1 || pid == 2 && msg contains \"x\"";
+
+        assert!(eval(expr, 1, None, "nothing"));
+        assert!(!eval(expr, 2, None, "nothing"));
+        assert!(eval(expr, 2, None, "x marks"));
+    }
+
+    #[test]
+    fn compares_pids_numerically() {
+        assert!(eval("pid >= 100 && pid < 200", 150, None, ""));
+        assert!(!eval("pid >= 100 && pid < 200", 200, None, ""));
+        assert!(eval("pid != 5", 6, None, ""));
+    }
+
+    #[test]
+    fn handles_unknown_process_names() {
+        assert!(!eval("name == \"app.exe\"", 1, None, ""));
+        assert!(eval("name != \"app.exe\"", 1, None, ""));
+        assert!(eval("name !~ /^svc/", 1, None, ""));
+    }
+
+    #[test]
+    fn supports_regex_flags_and_escaped_slashes() {
+        assert!(eval(r"msg ~ /a\/b/", 1, None, "path a/b"));
+        assert!(!eval("msg ~ /ERROR/", 1, None, "error"));
+        assert!(eval("msg ~ /ERROR/i", 1, None, "error"));
+        assert!(eval("msg ~ \"^err\"", 1, None, "error"));
+    }
+
+    #[test]
+    fn respects_case_sensitive_comparisons() {
+        let expr = FilterExpr::parse("msg contains \"Error\"", true).unwrap();
+
+        assert!(expr.evaluate(&FilterContext {
+            pid: 1,
+            name: None,
+            msg: "Error 5"
+        }));
+        assert!(!expr.evaluate(&FilterContext {
+            pid: 1,
+            name: None,
+            msg: "error 5"
+        }));
+    }
+
+    #[test]
+    fn reports_errors_with_columns() {
+        assert_eq!(
+            parse_error("pid == "),
+            "column 8: pid must be compared to a number"
+        );
+        assert_eq!(
+            parse_error("pid == 1 &&"),
+            "column 12: expected pid, name, msg, '!' or '(', found end of expression"
+        );
+        assert_eq!(
+            parse_error("(pid == 1"),
+            "column 10: expected ')', found end of expression"
+        );
+        assert_eq!(
+            parse_error("host == \"a\""),
+            "column 1: expected pid, name, msg, '!' or '(', found 'host'"
+        );
+        assert_eq!(
+            parse_error("msg ~ /x/q"),
+            "column 7: unknown regex flag 'q'"
+        );
+        assert_eq!(
+            parse_error("msg == 5"),
+            "column 8: expected a quoted string"
+        );
+        assert_eq!(
+            parse_error("pid ~ /1/"),
+            "column 5: pid supports ==, !=, <, <=, > and >=, found '~'"
+        );
+        assert_eq!(
+            parse_error("msg contains \"a"),
+            "column 14: unterminated string"
+        );
+        assert_eq!(
+            parse_error("pid == 1 pid"),
+            "column 10: expected '&&', '||' or end of expression, found 'pid'"
+        );
+        assert_eq!(parse_error("pid = 1"), "column 5: unexpected character '='");
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use crate::source::{DebugMessage, ProcessEventKind};
+use crate::template::{CONTINUATION_MARKER, Template};
+
+/// A decoded message ready to be formatted.
+pub struct Record<'a> {
+    pub message: &'a DebugMessage,
+    pub text: &'a str,
+    /// 1-based position among the written records.
+    pub seq: u64,
+    /// Milliseconds since the previous written record.
+    pub delta_ms: i64,
+    /// Set for process lifecycle records, which have no debug message text of their own.
+    pub event: Option<ProcessEventKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Jsonl,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "jsonl" | "ndjson" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!(
+                "Unknown format '{}'. Expected text, jsonl or csv.",
+                value
+            )),
+        }
+    }
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if ch < ' ' => {
+                let _ = write!(escaped, "\\u{:04x}", ch as u32);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_optional_string(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), json_string)
+}
+
+/// Quotes a CSV field as described in RFC 4180 when it contains a delimiter, quote or line break.
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl OutputFormat {
+    /// Header line written at the start of a new, empty output.
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Csv => Some("timestamp,pid,process_name,message,event"),
+            OutputFormat::Text | OutputFormat::Jsonl => None,
+        }
+    }
+
+    /// File extension used for files named after the output, e.g. by `--split-by-process`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "log",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Csv => "csv",
+        }
+    }
+
+    /// Like `format_record`, but in text format every line of a multi-line message gets
+    /// its own prefix, with continuation lines marked by `CONTINUATION_MARKER`. Other
+    /// formats keep one record with the line breaks escaped.
+    pub fn format_record_lines(&self, record: &Record, template: &Template) -> String {
+        if *self != OutputFormat::Text || !record.text.contains('\n') {
+            return self.format_record(record, template);
+        }
+
+        record
+            .text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                let text = if index == 0 {
+                    line.to_string()
+                } else {
+                    format!("{}{}", CONTINUATION_MARKER, line)
+                };
+                template.render(&Record {
+                    message: record.message,
+                    text: &text,
+                    seq: record.seq,
+                    delta_ms: record.delta_ms,
+                    event: record.event,
+                })
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Formats one record as a line without the trailing newline.
+    pub fn format_record(&self, record: &Record, template: &Template) -> String {
+        let message = record.message;
+        let text = record.text;
+        match self {
+            OutputFormat::Text => template.render(record),
+            OutputFormat::Jsonl => {
+                let (key, value) = match record.event {
+                    Some(event) => ("event", event.name()),
+                    None => ("message", text),
+                };
+                format!(
+                    "{{\"timestamp\":{},\"pid\":{},\"process_name\":{},\"{}\":{}}}",
+                    json_string(&message.timestamp.iso8601()),
+                    message.pid,
+                    json_optional_string(message.process_name.as_deref()),
+                    key,
+                    json_string(value)
+                )
+            }
+            OutputFormat::Csv => {
+                // Like JSON Lines, lifecycle records only fill the `event` column.
+                let (text, event) = match record.event {
+                    Some(event) => ("", event.name()),
+                    None => (text, ""),
+                };
+                format!(
+                    "{},{},{},{},{}",
+                    message.timestamp,
+                    message.pid,
+                    csv_field(message.process_name.as_deref().unwrap_or_default()),
+                    csv_field(text),
+                    event
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutputFormat, Record, csv_field, json_string};
+    use crate::source::{DebugMessage, ProcessEventKind, test_timestamp};
+    use crate::template::Template;
+
+    fn message(process_name: Option<&str>) -> DebugMessage {
+        let mut timestamp = test_timestamp();
+        timestamp.utc_offset_minutes = Some(120);
+        DebugMessage {
+            pid: 1234,
+            timestamp,
+            bytes: Vec::new(),
+            process_name: process_name.map(str::to_string),
+        }
+    }
+
+    fn format(format: OutputFormat, message: &DebugMessage, text: &str) -> String {
+        let record = Record {
+            message,
+            text,
+            seq: 1,
+            delta_ms: 0,
+            event: None,
+        };
+        format.format_record(&record, &Template::default())
+    }
+
+    #[test]
+    fn splits_multi_line_text_records() {
+        let message = message(Some("app.exe"));
+        let record = Record {
+            message: &message,
+            text: "first\r\nsecond\n\nfourth",
+            seq: 1,
+            delta_ms: 0,
+            event: None,
+        };
+        let template = Template::default();
+
+        assert_eq!(
+            OutputFormat::Text.format_record_lines(&record, &template),
+            concat!(
+                "[2026-06-01 09:08:07.006] [1234 app.exe] first\n",
+                "[2026-06-01 09:08:07.006] [1234 app.exe] | second\n",
+                "[2026-06-01 09:08:07.006] [1234 app.exe] | \n",
+                "[2026-06-01 09:08:07.006] [1234 app.exe] | fourth",
+            )
+        );
+        assert_eq!(
+            OutputFormat::Jsonl.format_record_lines(&record, &template),
+            OutputFormat::Jsonl.format_record(&record, &template)
+        );
+        assert!(
+            OutputFormat::Jsonl
+                .format_record_lines(&record, &template)
+                .contains(r#""message":"first\r\nsecond\n\nfourth""#)
+        );
+    }
+
+    #[test]
+    fn parses_format_names() {
+        assert_eq!("text".parse(), Ok(OutputFormat::Text));
+        assert_eq!("JSONL".parse(), Ok(OutputFormat::Jsonl));
+        assert_eq!("csv".parse(), Ok(OutputFormat::Csv));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(
+            json_string("say \"hi\"\\\n\t\u{1}é"),
+            "\"say \\\"hi\\\"\\\\\\n\\t\\u0001é\""
+        );
+    }
+
+    #[test]
+    fn formats_text_line() {
+        assert_eq!(
+            format(OutputFormat::Text, &message(None), "hello"),
+            "[2026-06-01 09:08:07.006] [1234 ?] hello"
+        );
+        assert_eq!(
+            format(OutputFormat::Text, &message(Some("app.exe")), "hello"),
+            "[2026-06-01 09:08:07.006] [1234 app.exe] hello"
+        );
+    }
+
+    #[test]
+    fn formats_json_line() {
+        assert_eq!(
+            format(OutputFormat::Jsonl, &message(Some("app.exe")), "a \"b\""),
+            "{\"timestamp\":\"2026-06-01T09:08:07.006+02:00\",\"pid\":1234,\
+             \"process_name\":\"app.exe\",\"message\":\"a \\\"b\\\"\"}"
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\r\nnext"), "\"line\r\nnext\"");
+    }
+
+    #[test]
+    fn formats_csv_row() {
+        assert_eq!(
+            format(OutputFormat::Csv, &message(Some("app.exe")), "a, \"b\""),
+            "2026-06-01 09:08:07.006,1234,app.exe,\"a, \"\"b\"\"\","
+        );
+        assert_eq!(
+            format(OutputFormat::Csv, &message(None), "text"),
+            "2026-06-01 09:08:07.006,1234,,text,"
+        );
+    }
+
+    #[test]
+    fn writes_header_only_for_csv() {
+        assert_eq!(
+            OutputFormat::Csv.header(),
+            Some("timestamp,pid,process_name,message,event")
+        );
+        assert_eq!(OutputFormat::Text.header(), None);
+        assert_eq!(OutputFormat::Jsonl.header(), None);
+    }
+
+    #[test]
+    fn formats_process_events() {
+        let message = message(Some("app.exe"));
+        let record = Record {
+            message: &message,
+            text: ProcessEventKind::Exited.text(),
+            seq: 1,
+            delta_ms: 0,
+            event: Some(ProcessEventKind::Exited),
+        };
+
+        assert_eq!(
+            OutputFormat::Jsonl.format_record(&record, &Template::default()),
+            "{\"timestamp\":\"2026-06-01T09:08:07.006+02:00\",\"pid\":1234,\
+             \"process_name\":\"app.exe\",\"event\":\"process_exited\"}"
+        );
+        assert_eq!(
+            OutputFormat::Text.format_record(&record, &Template::default()),
+            "[2026-06-01 09:08:07.006] [1234 app.exe] *** process exited ***"
+        );
+        assert_eq!(
+            OutputFormat::Csv.format_record(&record, &Template::default()),
+            "2026-06-01 09:08:07.006,1234,app.exe,,process_exited"
+        );
+    }
+
+    #[test]
+    fn formats_unknown_process_name_as_null() {
+        let line = format(OutputFormat::Jsonl, &message(None), "text");
+
+        assert!(line.contains("\"process_name\":null"));
+    }
+}
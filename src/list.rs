@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+
+use crate::format::json_string;
+use crate::target::glob_matches;
+use crate::winproc::ProcessInfo;
+
+/// Options of the `list` subcommand.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListArgs {
+    /// Glob matched against executable names; without `*` or `?` it matches substrings.
+    pub pattern: Option<String>,
+    pub json: bool,
+    /// Nest child processes under their parents.
+    pub tree: bool,
+}
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    if pattern.contains(['*', '?']) {
+        glob_matches(pattern, name)
+    } else {
+        glob_matches(&format!("*{}*", pattern), name)
+    }
+}
+
+/// Processes whose name matches `pattern`, sorted by PID.
+pub fn select_processes(processes: Vec<ProcessInfo>, pattern: Option<&str>) -> Vec<ProcessInfo> {
+    let mut selected: Vec<ProcessInfo> = processes
+        .into_iter()
+        .filter(|process| pattern.is_none_or(|pattern| matches_pattern(pattern, &process.exe_name)))
+        .collect();
+    selected.sort_by_key(|process| process.pid);
+    selected
+}
+
+/// Indices of `processes` in tree order, each with its depth. A process whose parent is
+/// not listed is a root, as is every process left over from a parent PID cycle.
+fn tree_order(processes: &[ProcessInfo]) -> Vec<(usize, usize)> {
+    let listed: HashSet<u32> = processes.iter().map(|process| process.pid).collect();
+    let mut children: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    let mut roots = Vec::new();
+    for (index, process) in processes.iter().enumerate() {
+        if process.parent_pid != process.pid && listed.contains(&process.parent_pid) {
+            children.entry(process.parent_pid).or_default().push(index);
+        } else {
+            roots.push(index);
+        }
+    }
+
+    let mut order = Vec::with_capacity(processes.len());
+    let mut visited = vec![false; processes.len()];
+    let mut pending: Vec<(usize, usize)> = roots.iter().rev().map(|&index| (index, 0)).collect();
+    for start in 0..processes.len() {
+        if pending.is_empty() && !visited[start] {
+            pending.push((start, 0));
+        }
+        while let Some((index, depth)) = pending.pop() {
+            if visited[index] {
+                continue;
+            }
+            visited[index] = true;
+            order.push((index, depth));
+            if let Some(child_indices) = children.get(&processes[index].pid) {
+                pending.extend(child_indices.iter().rev().map(|&child| (child, depth + 1)));
+            }
+        }
+    }
+    order
+}
+
+pub fn write_table(out: &mut impl Write, processes: &[ProcessInfo], tree: bool) -> io::Result<()> {
+    let order: Vec<(usize, usize)> = if tree {
+        tree_order(processes)
+    } else {
+        (0..processes.len()).map(|index| (index, 0)).collect()
+    };
+
+    writeln!(out, "{:>8} {:>8} {:>7}  NAME", "PID", "PPID", "THREADS")?;
+    for (index, depth) in order {
+        let process = &processes[index];
+        writeln!(
+            out,
+            "{:>8} {:>8} {:>7}  {}{}",
+            process.pid,
+            process.parent_pid,
+            process.thread_count,
+            "  ".repeat(depth),
+            process.exe_name
+        )?;
+    }
+    Ok(())
+}
+
+fn json_object(process: &ProcessInfo) -> String {
+    format!(
+        "{{\"pid\":{},\"parent_pid\":{},\"threads\":{},\"name\":{}",
+        process.pid,
+        process.parent_pid,
+        process.thread_count,
+        json_string(&process.exe_name)
+    )
+}
+
+/// Writes a JSON array; with `tree`, children are nested in a `children` array.
+pub fn write_json(out: &mut impl Write, processes: &[ProcessInfo], tree: bool) -> io::Result<()> {
+    if !tree {
+        let objects: Vec<String> = processes
+            .iter()
+            .map(|process| json_object(process) + "}")
+            .collect();
+        return writeln!(out, "[{}]", objects.join(","));
+    }
+
+    let mut json = String::from("[");
+    let mut open_depth = 0;
+    for (position, (index, depth)) in tree_order(processes).into_iter().enumerate() {
+        if position > 0 {
+            // Close the previous process and its finished subtrees.
+            if depth > open_depth {
+                json.push_str(",\"children\":[");
+            } else {
+                json.push('}');
+                for _ in depth..open_depth {
+                    json.push_str("]}");
+                }
+                json.push(',');
+            }
+        }
+        json.push_str(&json_object(&processes[index]));
+        open_depth = depth;
+    }
+    if !processes.is_empty() {
+        json.push('}');
+        for _ in 0..open_depth {
+            json.push_str("]}");
+        }
+    }
+    json.push(']');
+    writeln!(out, "{}", json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{select_processes, write_json, write_table};
+    use crate::winproc::ProcessInfo;
+
+    fn process(pid: u32, parent_pid: u32, exe_name: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent_pid,
+            thread_count: 2,
+            exe_name: exe_name.to_string(),
+        }
+    }
+
+    fn processes() -> Vec<ProcessInfo> {
+        vec![
+            process(30, 10, "helper.exe"),
+            process(10, 1, "app.exe"),
+            process(20, 1, "other.exe"),
+            process(40, 30, "grandchild.exe"),
+        ]
+    }
+
+    fn render(
+        write: impl Fn(&mut Vec<u8>, &[ProcessInfo], bool) -> std::io::Result<()>,
+        tree: bool,
+    ) -> String {
+        let mut out = Vec::new();
+        write(&mut out, &select_processes(processes(), None), tree).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn selects_by_substring_or_glob() {
+        let names = |pattern| -> Vec<String> {
+            select_processes(processes(), Some(pattern))
+                .into_iter()
+                .map(|process| process.exe_name)
+                .collect()
+        };
+
+        assert_eq!(names("APP"), vec!["app.exe"]);
+        assert_eq!(names("*er.exe"), vec!["other.exe", "helper.exe"]);
+        assert_eq!(names("h?lper.exe"), vec!["helper.exe"]);
+    }
+
+    #[test]
+    fn writes_table_sorted_by_pid() {
+        assert_eq!(
+            render(write_table, false),
+            concat!(
+                "     PID     PPID THREADS  NAME\n",
+                "      10        1       2  app.exe\n",
+                "      20        1       2  other.exe\n",
+                "      30       10       2  helper.exe\n",
+                "      40       30       2  grandchild.exe\n",
+            )
+        );
+    }
+
+    #[test]
+    fn writes_table_as_tree() {
+        assert_eq!(
+            render(write_table, true),
+            concat!(
+                "     PID     PPID THREADS  NAME\n",
+                "      10        1       2  app.exe\n",
+                "      30       10       2    helper.exe\n",
+                "      40       30       2      grandchild.exe\n",
+                "      20        1       2  other.exe\n",
+            )
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        assert_eq!(
+            render(write_json, false),
+            concat!(
+                "[{\"pid\":10,\"parent_pid\":1,\"threads\":2,\"name\":\"app.exe\"},",
+                "{\"pid\":20,\"parent_pid\":1,\"threads\":2,\"name\":\"other.exe\"},",
+                "{\"pid\":30,\"parent_pid\":10,\"threads\":2,\"name\":\"helper.exe\"},",
+                "{\"pid\":40,\"parent_pid\":30,\"threads\":2,\"name\":\"grandchild.exe\"}]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn writes_json_tree() {
+        assert_eq!(
+            render(write_json, true),
+            concat!(
+                "[{\"pid\":10,\"parent_pid\":1,\"threads\":2,\"name\":\"app.exe\",\"children\":[",
+                "{\"pid\":30,\"parent_pid\":10,\"threads\":2,\"name\":\"helper.exe\",\"children\":[",
+                "{\"pid\":40,\"parent_pid\":30,\"threads\":2,\"name\":\"grandchild.exe\"}]}]},",
+                "{\"pid\":20,\"parent_pid\":1,\"threads\":2,\"name\":\"other.exe\"}]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn lists_parent_pid_cycles() {
+        let mut out = Vec::new();
+        let cycle = vec![process(5, 6, "a.exe"), process(6, 5, "b.exe")];
+        write_table(&mut out, &cycle, true).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+    }
+}
@@ -1,56 +1,356 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+mod capture;
+mod encoding;
+mod filter;
+mod filterexpr;
+mod format;
+mod list;
+#[cfg(windows)]
 mod processiter;
+mod queue;
+mod reassemble;
+mod replay;
+mod rotate;
+mod source;
+mod split;
+mod target;
+mod template;
+mod units;
+#[cfg(windows)]
 mod winapi;
 mod winproc;
-use winproc::find_process_ids_by_name;
+use winproc::{current_timestamp, process_snapshot};
 
-use crate::winproc::{CaptureTarget, SharedTargetPids, capture_debug_output};
+use crate::capture::{
+    CaptureOptions, CaptureStats, CaptureTarget, FlushPolicy, SharedTargetPids, Sink, SinkOutput,
+    capture_debug_output, capture_in_background,
+};
+use crate::encoding::Encoding;
+use crate::filter::{FilterRules, MessageFilter, build_regex};
+use crate::format::OutputFormat;
+use crate::list::ListArgs;
+use crate::queue::OverflowPolicy;
+use crate::reassemble::{REASSEMBLY_WINDOW, ReassemblingSource};
+use crate::replay::ReplaySource;
+use crate::rotate::{RotatingFile, RotationPolicy};
+use crate::source::{
+    MessageSource, ProcessEvent, ProcessEventKind, STOP_DRAIN_TIMEOUT, Timestamp, UntilStopped,
+    WithProcessEvents,
+};
+use crate::split::{ProcessFiles, SplitKey};
+use crate::target::{ProcessExclusions, TargetSpec};
+use crate::template::Template;
+use crate::winproc::DbwinSource;
 
 const PID_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+/// How often `--wait` reports that it is still waiting.
+const WAIT_STATUS_INTERVAL: Duration = Duration::from_secs(10);
+/// Exit code when `--wait-timeout` passes before a target process starts.
+const EXIT_WAIT_TIMEOUT: i32 = 2;
+/// Events buffered between the DBWIN reader and the output writer, see `--queue-size`.
+const DEFAULT_QUEUE_SIZE: usize = 10_000;
 
-#[derive(Debug)]
+/// An `--output` destination; `path` is `None` for stdout (`-`).
+#[derive(Debug, PartialEq)]
+struct OutputSpec {
+    path: Option<PathBuf>,
+    format: OutputFormat,
+}
+
+#[derive(Debug, Default)]
 struct AppArgs {
-    app_name: Option<String>,
-    pid: Option<u32>,
+    /// Empty to capture all processes.
+    targets: TargetSpec,
+    /// Processes left out when capturing all processes.
+    exclusions: ProcessExclusions,
     wait: bool,
+    /// Give up `--wait` after this long.
+    wait_timeout: Option<Duration>,
     follow_name: bool,
-    output_file: Option<PathBuf>,
+    /// Stop once all target processes have been gone for this grace period.
+    exit_when_gone: Option<Duration>,
+    /// Never empty; stdout alone when no `--output` is given.
+    outputs: Vec<OutputSpec>,
+    /// `--split-by-process` directory and the format of its files.
+    split_output: Option<OutputSpec>,
+    split_key: SplitKey,
     append: bool,
+    rotation: RotationPolicy,
     highlight_words: Vec<String>,
+    filter: MessageFilter,
+    template: Option<Template>,
+    encoding: Option<Encoding>,
+    split_lines: bool,
+    flush: Option<FlushPolicy>,
+    queue_size: Option<usize>,
+    overflow: Option<OverflowPolicy>,
+    replay_file: Option<PathBuf>,
+    speed: Option<f64>,
+    /// Set for the `list` subcommand, which only prints processes.
+    list: Option<ListArgs>,
     help: bool,
 }
 
-fn parse_pid(value: &str) -> Result<u32, String> {
+fn add_target_pid(targets: &mut TargetSpec, value: &str) -> Result<(), String> {
+    let pid = parse_pid("--pid", value)?;
+    if !targets.pids.contains(&pid) {
+        targets.pids.push(pid);
+    }
+    Ok(())
+}
+
+fn add_name_regex(targets: &mut TargetSpec, pattern: &str) -> Result<(), String> {
+    if pattern.is_empty() {
+        return Err("--name-regex requires a non-empty pattern.".to_string());
+    }
+    targets.name_regexes.push(build_regex(pattern, false)?);
+    Ok(())
+}
+
+fn add_excluded_process(exclusions: &mut ProcessExclusions, name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("--exclude-process requires a non-empty process name.".to_string());
+    }
+    if !exclusions
+        .names
+        .iter()
+        .any(|excluded| excluded.eq_ignore_ascii_case(name))
+    {
+        exclusions.names.push(name.to_string());
+    }
+    Ok(())
+}
+
+fn parse_pid(option: &str, value: &str) -> Result<u32, String> {
     match value.parse::<u32>() {
         Ok(pid) if pid > 0 => Ok(pid),
-        Ok(_) => Err("--pid must be greater than 0.".to_string()),
+        Ok(_) => Err(format!("{} must be greater than 0.", option)),
         Err(_) => Err(format!("Invalid PID '{}'.", value)),
     }
 }
 
-fn parse_output_file(value: &str) -> Result<PathBuf, String> {
+/// Parses `[format:]path`, where `-` is stdout; the format defaults to `--format`.
+fn parse_output(value: &str) -> Result<(Option<PathBuf>, Option<OutputFormat>), String> {
+    let (format, path) = match value.split_once(':') {
+        Some((format, path)) => match format.parse::<OutputFormat>() {
+            Ok(format) => (Some(format), path),
+            Err(_) => (None, value),
+        },
+        None => (None, value),
+    };
+
+    match path {
+        "" => Err("--output requires a non-empty file path.".to_string()),
+        "-" => Ok((None, format)),
+        path => Ok((Some(PathBuf::from(path)), format)),
+    }
+}
+
+fn add_output(
+    outputs: &mut Vec<(Option<PathBuf>, Option<OutputFormat>)>,
+    value: &str,
+) -> Result<(), String> {
+    let (path, format) = parse_output(value)?;
+    if outputs.iter().any(|(existing, _)| *existing == path) {
+        return Err(format!(
+            "--output '{}' is specified more than once.",
+            path.as_deref().map_or("-".into(), Path::to_string_lossy)
+        ));
+    }
+
+    outputs.push((path, format));
+    Ok(())
+}
+
+fn set_split_dir(split_dir: &mut Option<PathBuf>, value: &str) -> Result<(), String> {
+    if split_dir.is_some() {
+        return Err("--split-by-process can only be specified once.".to_string());
+    }
+    if value.is_empty() {
+        return Err("--split-by-process requires a non-empty directory path.".to_string());
+    }
+
+    *split_dir = Some(PathBuf::from(value));
+    Ok(())
+}
+
+fn set_rotate_size(rotation: &mut RotationPolicy, value: &str) -> Result<(), String> {
+    if rotation.max_bytes.is_some() {
+        return Err("--rotate-size can only be specified once.".to_string());
+    }
+
+    match units::parse_size(value)? {
+        0 => Err("--rotate-size must be greater than 0.".to_string()),
+        max_bytes => {
+            rotation.max_bytes = Some(max_bytes);
+            Ok(())
+        }
+    }
+}
+
+fn set_encoding(encoding: &mut Option<Encoding>, value: &str) -> Result<(), String> {
+    if encoding.is_some() {
+        return Err("--encoding can only be specified once.".to_string());
+    }
+
+    *encoding = Some(value.parse()?);
+    Ok(())
+}
+
+fn set_flush(flush: &mut Option<FlushPolicy>, value: &str) -> Result<(), String> {
+    if flush.is_some() {
+        return Err("--flush can only be specified once.".to_string());
+    }
+
+    *flush = Some(value.parse()?);
+    Ok(())
+}
+
+fn set_queue_size(queue_size: &mut Option<usize>, value: &str) -> Result<(), String> {
+    if queue_size.is_some() {
+        return Err("--queue-size can only be specified once.".to_string());
+    }
+
+    match value.parse::<usize>() {
+        Ok(size) if size > 0 => {
+            *queue_size = Some(size);
+            Ok(())
+        }
+        _ => Err(format!(
+            "Invalid queue size '{}'. Expected a positive number of messages.",
+            value
+        )),
+    }
+}
+
+fn set_overflow(overflow: &mut Option<OverflowPolicy>, value: &str) -> Result<(), String> {
+    if overflow.is_some() {
+        return Err("--on-overflow can only be specified once.".to_string());
+    }
+
+    *overflow = Some(value.parse()?);
+    Ok(())
+}
+
+fn set_wait_timeout(wait_timeout: &mut Option<Duration>, value: &str) -> Result<(), String> {
+    if wait_timeout.is_some() {
+        return Err("--wait-timeout can only be specified once.".to_string());
+    }
+
+    *wait_timeout = Some(units::parse_duration(value)?);
+    Ok(())
+}
+
+/// `grace` is `None` for a bare `--exit-when-gone`.
+fn set_exit_when_gone(
+    exit_when_gone: &mut Option<Duration>,
+    grace: Option<&str>,
+) -> Result<(), String> {
+    if exit_when_gone.is_some() {
+        return Err("--exit-when-gone can only be specified once.".to_string());
+    }
+
+    *exit_when_gone = Some(grace.map_or(Ok(Duration::ZERO), units::parse_duration)?);
+    Ok(())
+}
+
+fn set_rotate_interval(rotation: &mut RotationPolicy, value: &str) -> Result<(), String> {
+    if rotation.interval.is_some() {
+        return Err("--rotate-interval can only be specified once.".to_string());
+    }
+
+    let interval = units::parse_duration(value)?;
+    if interval.is_zero() {
+        return Err("--rotate-interval must be greater than 0.".to_string());
+    }
+
+    rotation.interval = Some(interval);
+    Ok(())
+}
+
+fn set_rotate_keep(rotation: &mut RotationPolicy, value: &str) -> Result<(), String> {
+    if rotation.keep.is_some() {
+        return Err("--rotate-keep can only be specified once.".to_string());
+    }
+
+    match value.parse::<usize>() {
+        Ok(keep) if keep > 0 => {
+            rotation.keep = Some(keep);
+            Ok(())
+        }
+        Ok(_) => Err("--rotate-keep must be greater than 0.".to_string()),
+        Err(_) => Err(format!("Invalid file count '{}'.", value)),
+    }
+}
+
+fn parse_replay_file(value: &str) -> Result<PathBuf, String> {
     if value.is_empty() {
-        return Err("--output requires a non-empty file path.".to_string());
+        return Err("replay requires a non-empty log file path.".to_string());
     }
 
     Ok(PathBuf::from(value))
 }
 
-fn set_output_file(output_file: &mut Option<PathBuf>, value: &str) -> Result<(), String> {
-    if output_file.is_some() {
-        return Err("--output can only be specified once.".to_string());
+fn parse_speed(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        Ok(_) => Err("--speed must be greater than 0.".to_string()),
+        Err(_) => Err(format!("Invalid speed '{}'.", value)),
+    }
+}
+
+fn set_speed(speed: &mut Option<f64>, value: &str) -> Result<(), String> {
+    if speed.is_some() {
+        return Err("--speed can only be specified once.".to_string());
+    }
+
+    *speed = Some(parse_speed(value)?);
+    Ok(())
+}
+
+fn set_format(format: &mut Option<OutputFormat>, value: &str) -> Result<(), String> {
+    if format.is_some() {
+        return Err("--format can only be specified once.".to_string());
+    }
+
+    *format = Some(value.parse()?);
+    Ok(())
+}
+
+fn parse_filter_text(option: &str, value: String) -> Result<String, String> {
+    if value.is_empty() {
+        return Err(format!("{} requires a non-empty value.", option));
+    }
+
+    Ok(value)
+}
+
+fn set_filter_expression(filter_rules: &mut FilterRules, value: String) -> Result<(), String> {
+    if filter_rules.expression.is_some() {
+        return Err("--filter can only be specified once.".to_string());
+    }
+
+    filter_rules.expression = Some(parse_filter_text("--filter", value)?);
+    Ok(())
+}
+
+fn set_template(template: &mut Option<Template>, value: &str) -> Result<(), String> {
+    if template.is_some() {
+        return Err("--template can only be specified once.".to_string());
     }
 
-    *output_file = Some(parse_output_file(value)?);
+    *template = Some(value.parse()?);
     Ok(())
 }
 
@@ -69,37 +369,184 @@ fn parse_highlight_words(value: &str) -> Result<Vec<String>, String> {
     Ok(words)
 }
 
+fn parse_list_args(args: impl Iterator<Item = String>) -> Result<AppArgs, String> {
+    let mut list = ListArgs::default();
+    let mut help = false;
+    for arg in args {
+        if arg == "--help" || arg == "-h" {
+            help = true;
+        } else if arg == "--json" {
+            list.json = true;
+        } else if arg == "--tree" {
+            list.tree = true;
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown list option '{}'.", arg));
+        } else if list.pattern.is_some() {
+            return Err("list accepts at most one pattern.".to_string());
+        } else {
+            list.pattern = Some(arg);
+        }
+    }
+
+    Ok(AppArgs {
+        list: Some(list),
+        help,
+        ..AppArgs::default()
+    })
+}
+
 fn parse_args<I>(args: I) -> Result<AppArgs, String>
 where
     I: IntoIterator<Item = String>,
 {
-    let mut app_name = None;
-    let mut pid = None;
+    let mut targets = TargetSpec::default();
+    let mut exclusions = ProcessExclusions::default();
     let mut wait = false;
+    let mut wait_timeout = None;
+    let mut exit_when_gone = None;
     let mut follow_name = false;
-    let mut output_file = None;
+    let mut outputs = Vec::new();
+    let mut split_dir = None;
+    let mut split_key = SplitKey::Pid;
     let mut append = false;
+    let mut rotation = RotationPolicy::default();
     let mut highlight_words = Vec::new();
+    let mut filter_rules = FilterRules::default();
+    let mut format = None;
+    let mut template = None;
+    let mut encoding = None;
+    let mut split_lines = false;
+    let mut flush = None;
+    let mut queue_size = None;
+    let mut overflow = None;
+    let mut replay_file = None;
+    let mut speed = None;
     let mut help = false;
-    let mut args = args.into_iter();
+    let mut args = args.into_iter().peekable();
+    if args.next_if(|arg| arg == "list").is_some() {
+        return parse_list_args(args);
+    }
+    if args.next_if(|arg| arg == "replay").is_some() {
+        let value = args
+            .next()
+            .ok_or_else(|| "replay requires a log file path.".to_string())?;
+        replay_file = Some(parse_replay_file(&value)?);
+    }
+
     while let Some(arg) = args.next() {
         if arg == "--help" || arg == "-h" {
             help = true;
         } else if arg == "--wait" {
             wait = true;
+        } else if arg == "--wait-timeout" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--wait-timeout requires a duration, e.g. 30s.".to_string())?;
+            set_wait_timeout(&mut wait_timeout, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--wait-timeout=") {
+            set_wait_timeout(&mut wait_timeout, value)?;
         } else if arg == "--follow-name" {
             follow_name = true;
+        } else if arg == "--exit-when-gone" {
+            set_exit_when_gone(&mut exit_when_gone, None)?;
+        } else if let Some(value) = arg.strip_prefix("--exit-when-gone=") {
+            set_exit_when_gone(&mut exit_when_gone, Some(value))?;
+        } else if arg == "--follow-children" {
+            targets.follow_children = true;
         } else if arg == "--append" {
             append = true;
+        } else if arg == "--case-sensitive" {
+            filter_rules.case_sensitive = true;
+        } else if arg == "--include" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--include requires a text value.".to_string())?;
+            filter_rules
+                .include_texts
+                .push(parse_filter_text("--include", value)?);
+        } else if let Some(value) = arg.strip_prefix("--include=") {
+            filter_rules
+                .include_texts
+                .push(parse_filter_text("--include", value.to_string())?);
+        } else if arg == "--include-regex" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--include-regex requires a pattern.".to_string())?;
+            filter_rules
+                .include_regexes
+                .push(parse_filter_text("--include-regex", value)?);
+        } else if let Some(value) = arg.strip_prefix("--include-regex=") {
+            filter_rules
+                .include_regexes
+                .push(parse_filter_text("--include-regex", value.to_string())?);
+        } else if arg == "--filter" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--filter requires an expression.".to_string())?;
+            set_filter_expression(&mut filter_rules, value)?;
+        } else if let Some(value) = arg.strip_prefix("--filter=") {
+            set_filter_expression(&mut filter_rules, value.to_string())?;
+        } else if arg == "--exclude" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--exclude requires a text value.".to_string())?;
+            filter_rules
+                .exclude_texts
+                .push(parse_filter_text("--exclude", value)?);
+        } else if let Some(value) = arg.strip_prefix("--exclude=") {
+            filter_rules
+                .exclude_texts
+                .push(parse_filter_text("--exclude", value.to_string())?);
+        } else if arg == "--exclude-regex" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--exclude-regex requires a pattern.".to_string())?;
+            filter_rules
+                .exclude_regexes
+                .push(parse_filter_text("--exclude-regex", value)?);
+        } else if let Some(value) = arg.strip_prefix("--exclude-regex=") {
+            filter_rules
+                .exclude_regexes
+                .push(parse_filter_text("--exclude-regex", value.to_string())?);
         } else if arg == "--output" || arg == "-o" {
             let value = args
                 .next()
                 .ok_or_else(|| "--output requires a file path.".to_string())?;
-            set_output_file(&mut output_file, &value)?;
+            add_output(&mut outputs, &value)?;
         } else if let Some(value) = arg.strip_prefix("--output=") {
-            set_output_file(&mut output_file, value)?;
+            add_output(&mut outputs, value)?;
         } else if let Some(value) = arg.strip_prefix("-o=") {
-            set_output_file(&mut output_file, value)?;
+            add_output(&mut outputs, value)?;
+        } else if arg == "--split-by-process" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--split-by-process requires a directory path.".to_string())?;
+            set_split_dir(&mut split_dir, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--split-by-process=") {
+            set_split_dir(&mut split_dir, value)?;
+        } else if arg == "--split-by-name" {
+            split_key = SplitKey::Name;
+        } else if arg == "--rotate-size" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--rotate-size requires a size, e.g. 50MB.".to_string())?;
+            set_rotate_size(&mut rotation, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--rotate-size=") {
+            set_rotate_size(&mut rotation, value)?;
+        } else if arg == "--rotate-interval" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--rotate-interval requires a duration, e.g. 1h.".to_string())?;
+            set_rotate_interval(&mut rotation, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--rotate-interval=") {
+            set_rotate_interval(&mut rotation, value)?;
+        } else if arg == "--rotate-keep" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--rotate-keep requires a file count.".to_string())?;
+            set_rotate_keep(&mut rotation, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--rotate-keep=") {
+            set_rotate_keep(&mut rotation, value)?;
         } else if arg == "--highlight" {
             let value = args
                 .next()
@@ -107,54 +554,214 @@ where
             highlight_words.extend(parse_highlight_words(&value)?);
         } else if let Some(value) = arg.strip_prefix("--highlight=") {
             highlight_words.extend(parse_highlight_words(value)?);
+        } else if arg == "--format" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--format requires text, jsonl or csv.".to_string())?;
+            set_format(&mut format, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--format=") {
+            set_format(&mut format, value)?;
+        } else if arg == "--split-lines" {
+            split_lines = true;
+        } else if arg == "--flush" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--flush requires every, interval=<ms> or on-exit.".to_string())?;
+            set_flush(&mut flush, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--flush=") {
+            set_flush(&mut flush, value)?;
+        } else if arg == "--queue-size" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--queue-size requires a number of messages.".to_string())?;
+            set_queue_size(&mut queue_size, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--queue-size=") {
+            set_queue_size(&mut queue_size, value)?;
+        } else if arg == "--on-overflow" {
+            let value = args.next().ok_or_else(|| {
+                "--on-overflow requires block, drop-oldest or drop-newest.".to_string()
+            })?;
+            set_overflow(&mut overflow, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--on-overflow=") {
+            set_overflow(&mut overflow, value)?;
+        } else if arg == "--encoding" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--encoding requires an encoding name, e.g. cp1252.".to_string())?;
+            set_encoding(&mut encoding, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--encoding=") {
+            set_encoding(&mut encoding, value)?;
+        } else if arg == "--template" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--template requires a line template.".to_string())?;
+            set_template(&mut template, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--template=") {
+            set_template(&mut template, value)?;
+        } else if arg == "--speed" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--speed requires a multiplier.".to_string())?;
+            set_speed(&mut speed, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--speed=") {
+            set_speed(&mut speed, value)?;
         } else if arg == "--pid" {
             let value = args
                 .next()
                 .ok_or_else(|| "--pid requires a PID value.".to_string())?;
-            if pid.is_some() {
-                return Err("--pid can only be specified once.".to_string());
-            }
-            pid = Some(parse_pid(&value)?);
+            add_target_pid(&mut targets, &value)?;
         } else if let Some(value) = arg.strip_prefix("--pid=") {
-            if pid.is_some() {
-                return Err("--pid can only be specified once.".to_string());
+            add_target_pid(&mut targets, value)?;
+        } else if arg == "--exclude-process" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--exclude-process requires a process name.".to_string())?;
+            add_excluded_process(&mut exclusions, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--exclude-process=") {
+            add_excluded_process(&mut exclusions, value)?;
+        } else if arg == "--exclude-pid" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--exclude-pid requires a PID value.".to_string())?;
+            exclusions.pids.insert(parse_pid("--exclude-pid", &value)?);
+        } else if let Some(value) = arg.strip_prefix("--exclude-pid=") {
+            exclusions.pids.insert(parse_pid("--exclude-pid", value)?);
+        } else if arg == "--name-regex" {
+            let value = args
+                .next()
+                .ok_or_else(|| "--name-regex requires a pattern.".to_string())?;
+            add_name_regex(&mut targets, &value)?;
+        } else if let Some(value) = arg.strip_prefix("--name-regex=") {
+            add_name_regex(&mut targets, value)?;
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown option '{}'.", arg));
+        } else if !targets
+            .names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&arg))
+        {
+            targets.names.push(arg);
+        }
+    }
+
+    if replay_file.is_some() {
+        if targets.has_names() {
+            return Err("replay does not accept a process name; use --pid to filter.".to_string());
+        }
+
+        for (enabled, option) in [
+            (wait, "--wait"),
+            (follow_name, "--follow-name"),
+            (targets.follow_children, "--follow-children"),
+            (exit_when_gone.is_some(), "--exit-when-gone"),
+            (queue_size.is_some(), "--queue-size"),
+            (overflow.is_some(), "--on-overflow"),
+        ] {
+            if enabled {
+                return Err(format!("{} cannot be used with replay.", option));
             }
-            pid = Some(parse_pid(value)?);
-        } else if app_name.is_none() {
-            app_name = Some(arg);
-        } else {
-            return Err(format!("Unexpected argument '{}'.", arg));
         }
+    } else if speed.is_some() {
+        return Err("--speed can only be used with replay.".to_string());
+    }
+
+    if !exclusions.is_empty() && !targets.is_empty() {
+        return Err(
+            "--exclude-process and --exclude-pid can only be used when capturing all processes."
+                .to_string(),
+        );
     }
 
-    if app_name.is_some() && pid.is_some() {
-        return Err("Specify either a process name or --pid, not both.".to_string());
+    if exit_when_gone.is_some() && targets.is_empty() {
+        return Err("--exit-when-gone requires a process name or --pid.".to_string());
+    }
+
+    if wait_timeout.is_some() && !wait {
+        return Err("--wait-timeout requires --wait.".to_string());
+    }
+
+    if targets.follow_children && targets.is_empty() {
+        return Err("--follow-children requires a process name or --pid.".to_string());
+    }
+
+    if !targets.has_names() {
+        for (enabled, option) in [(wait, "--wait"), (follow_name, "--follow-name")] {
+            if enabled && targets.pids.is_empty() {
+                return Err(format!("{} requires a process name.", option));
+            }
+            if enabled {
+                return Err(format!("{} can only be used with a process name.", option));
+            }
+        }
     }
 
-    if wait && pid.is_some() {
-        return Err("--wait can only be used with a process name.".to_string());
+    let filter = MessageFilter::new(&filter_rules)?;
+
+    if outputs.is_empty() {
+        outputs.push((None, None));
     }
+    let outputs: Vec<OutputSpec> = outputs
+        .into_iter()
+        .map(|(path, output_format)| OutputSpec {
+            path,
+            format: output_format.or(format).unwrap_or_default(),
+        })
+        .collect();
+    let split_output = split_dir.map(|path| OutputSpec {
+        path: Some(path),
+        format: format.unwrap_or_default(),
+    });
 
-    if follow_name && pid.is_some() {
-        return Err("--follow-name can only be used with a process name.".to_string());
+    if split_key == SplitKey::Name && split_output.is_none() {
+        return Err("--split-by-name requires --split-by-process <dir>.".to_string());
     }
 
-    if follow_name && app_name.is_none() {
-        return Err("--follow-name requires a process name.".to_string());
+    if template.is_some()
+        && !outputs
+            .iter()
+            .chain(&split_output)
+            .any(|output| output.format == OutputFormat::Text)
+    {
+        return Err("--template can only be used with the text format.".to_string());
     }
 
-    if append && output_file.is_none() {
+    let has_output_file =
+        split_output.is_some() || outputs.iter().any(|output| output.path.is_some());
+    if append && !has_output_file {
         return Err("--append requires --output <file>.".to_string());
     }
 
+    if rotation.is_enabled() && !has_output_file {
+        return Err("--rotate-size and --rotate-interval require --output <file>.".to_string());
+    }
+
+    if rotation.keep.is_some() && !rotation.is_enabled() {
+        return Err("--rotate-keep requires --rotate-size or --rotate-interval.".to_string());
+    }
+
     Ok(AppArgs {
-        app_name,
-        pid,
+        targets,
+        exclusions,
         wait,
+        wait_timeout,
         follow_name,
-        output_file,
+        exit_when_gone,
+        outputs,
+        split_output,
+        split_key,
         append,
+        rotation,
         highlight_words,
+        filter,
+        template,
+        encoding,
+        split_lines,
+        flush,
+        queue_size,
+        overflow,
+        replay_file,
+        speed,
+        list: None,
         help,
     })
 }
@@ -168,32 +775,115 @@ fn print_help(program_name: &str) {
     println!();
     println!("Usage:");
     println!(
-        "  {} [process_name] [--wait] [--follow-name] [--highlight <word[,word...]>]",
+        "  {} [process_name...] [--pid <pid>...] [--wait] [--follow-name] [--highlight <word[,word...]>]",
         program_name
     );
     println!(
-        "  {} --pid <pid> [--highlight <word[,word...]>]",
+        "  {} replay <file> [--pid <pid>] [--speed <multiplier>] [--highlight <word[,word...]>]",
         program_name
     );
+    println!("  {} list [pattern] [--tree] [--json]", program_name);
     println!("  {} --help", program_name);
     println!();
     println!("Arguments:");
     println!(
-        "  process_name    Optional executable name to monitor across all current matching PIDs"
+        "  process_name    Optional executable names to monitor across all current matching PIDs;"
     );
+    println!("                  * and ? match any characters, e.g. worker-*.exe");
     println!();
     println!("Options:");
-    println!("  --pid <pid>     Monitor an existing process by PID");
-    println!("  --wait          Wait for process_name to start before capturing output");
+    println!("  --pid <pid>     Monitor an existing process by PID; repeatable");
+    println!("  --exclude-process <name>");
+    println!(
+        "                  Skip processes with this name or glob when capturing all processes"
+    );
+    println!("  --exclude-pid <pid>");
+    println!("                  Skip this PID when capturing all processes; both are repeatable");
+    println!("  --name-regex <regex>");
+    println!("                  Also monitor processes whose name matches <regex>; repeatable");
+    println!("  --wait          Wait for a process_name to start before capturing output");
+    println!("  --wait-timeout <duration>");
+    println!("                  Give up --wait after e.g. 30s or 5m and exit with code 2");
     println!("  --follow-name   Keep tracking new and restarted processes matching process_name");
-    println!("  -o, --output <file>");
-    println!("                  Write captured debug output to a file instead of stdout");
-    println!("  --append        Append to --output instead of replacing it");
+    println!("  --exit-when-gone[=<grace>]");
+    println!("                  Stop once all target processes have exited, optionally after a");
+    println!("                  grace period such as 10s that allows for restarts");
+    println!("  --follow-children");
+    println!("                  Also capture all descendants of the targeted processes");
+    println!("  -o, --output [text:|jsonl:|csv:]<file>");
+    println!("                  Write captured debug output to a file instead of stdout;");
+    println!("                  repeatable, use - for stdout and a prefix to override --format");
+    println!("  --split-by-process <dir>");
+    println!("                  Also write each PID's messages to <dir>/<name>-<pid>.log");
+    println!("  --split-by-name Split into one <dir>/<name>.log per process name instead");
+    println!("  --append        Append to output files instead of replacing them");
+    println!("  --rotate-size <size>");
+    println!(
+        "                  Roll --output over to <name>.1.<ext> once it reaches size, e.g. 50MB"
+    );
+    println!("  --rotate-interval <duration>");
+    println!("                  Roll --output over after duration, e.g. 30m, 1h or 1d");
+    println!("  --rotate-keep <count>");
+    println!("                  Keep at most count rolled files and delete older ones");
+    println!("  --format <text|jsonl|csv>");
+    println!(
+        "                  Write plain text lines (default), JSON Lines, or CSV rows to each output"
+    );
+    println!("  --split-lines   Write each line of a multi-line message with its own prefix,");
+    println!(
+        "                  marking continuation lines with \"{}\"",
+        template::CONTINUATION_MARKER
+    );
+    println!("  --encoding <encoding>");
+    println!(
+        "                  Decode messages as lossy (default), utf8 (drops invalid messages),"
+    );
+    println!("                  oem, ansi, cp1252, latin1, cp1251, cp437, cp850 or cp866");
+    println!("  --flush <every|interval=<ms>|on-exit>");
+    println!("                  Flush outputs after every message (default), at most every ms");
+    println!("                  milliseconds, or only when capture ends");
+    println!("  --queue-size <n>");
+    println!(
+        "                  Buffer up to n messages for the output writer (default {})",
+        DEFAULT_QUEUE_SIZE
+    );
+    println!("  --on-overflow <block|drop-oldest|drop-newest>");
+    println!(
+        "                  When the buffer is full, wait for the writer (default) or drop messages"
+    );
+    println!("  --template <template>");
+    println!(
+        "                  Layout of text lines, default \"{}\"",
+        template::DEFAULT_TEMPLATE
+    );
+    println!(
+        "                  Placeholders: {{time}} {{utc}} {{pid}} {{name}} {{seq}} {{delta}} {{msg}}"
+    );
+    println!("                  Padding: {{pid:>6}} {{name:<12.12}} {{seq:06}} {{msg:^20}}");
+    println!("  --include <text>");
+    println!("                  Only write messages containing text (repeatable)");
+    println!("  --include-regex <pattern>");
+    println!("                  Only write messages matching the regular expression (repeatable)");
+    println!("  --exclude <text>");
+    println!("                  Drop messages containing text (repeatable)");
+    println!("  --exclude-regex <pattern>");
+    println!("                  Drop messages matching the regular expression (repeatable)");
+    println!("  --filter <expr>");
+    println!("                  Only write messages matching the expression, e.g.");
+    println!(
+        "                  pid == 1234 && (msg ~ /timeout/i || name == \"svc.exe\") && !msg contains \"heartbeat\""
+    );
+    println!("  --case-sensitive");
+    println!("                  Match --include, --exclude and --filter text case-sensitively");
     println!("  --highlight <word[,word...]>");
-    println!("                  Highlight matching words in blue when writing to stdout");
+    println!("                  Highlight matching words in blue in text written to stdout");
+    println!("  --speed <multiplier>");
+    println!("                  Replay with the original message timing divided by multiplier");
+    println!("  --tree          list: show child processes under their parents");
+    println!("  --json          list: print a JSON array instead of a table");
     println!("  -h, --help      Show this help message and exit");
     println!();
-    println!("Press Esc while capturing to exit.");
+    println!("Press Esc or Ctrl+C while capturing to exit.");
 }
 
 fn format_pids(pids: &HashSet<u32>) -> String {
@@ -209,175 +899,504 @@ fn format_pids(pids: &HashSet<u32>) -> String {
         .join(", ")
 }
 
-fn current_target_pids(app_name: &str) -> HashSet<u32> {
-    find_process_ids_by_name(app_name).into_iter().collect()
+/// Waits until a target process is running; `None` if `timeout` passes first.
+fn wait_for_target_processes(
+    targets: &TargetSpec,
+    timeout: Option<Duration>,
+) -> Option<HashMap<u32, String>> {
+    let label = targets.describe_names();
+    poll_processes(
+        timeout,
+        PID_SCAN_INTERVAL,
+        || targets.current_processes(),
+        |elapsed| {
+            eprintln!(
+                "Still waiting for {} ({}s elapsed)...",
+                label,
+                elapsed.as_secs()
+            )
+        },
+    )
 }
 
-fn wait_for_target_pids(app_name: &str) -> HashSet<u32> {
+/// Calls `poll` every `interval` until it finds processes, reporting the elapsed time to
+/// `status` every `WAIT_STATUS_INTERVAL`; `None` once `timeout` has passed.
+fn poll_processes(
+    timeout: Option<Duration>,
+    interval: Duration,
+    mut poll: impl FnMut() -> HashMap<u32, String>,
+    mut status: impl FnMut(Duration),
+) -> Option<HashMap<u32, String>> {
+    let started = Instant::now();
+    let mut next_status = WAIT_STATUS_INTERVAL;
     loop {
-        let pids = current_target_pids(app_name);
-        if !pids.is_empty() {
-            return pids;
+        let processes = poll();
+        if !processes.is_empty() {
+            return Some(processes);
+        }
+
+        let elapsed = started.elapsed();
+        if timeout.is_some_and(|timeout| elapsed >= timeout) {
+            return None;
+        }
+        if elapsed >= next_status {
+            status(elapsed);
+            next_status += WAIT_STATUS_INTERVAL;
         }
-        thread::sleep(PID_SCAN_INTERVAL);
+        let remaining = timeout.map_or(interval, |timeout| timeout - elapsed);
+        thread::sleep(interval.min(remaining));
     }
 }
 
-fn start_pid_scanner(app_name: String, target_pids: SharedTargetPids) {
+/// Lifecycle events for processes that appear in `next` or are missing from it.
+fn process_events(
+    previous: &HashMap<u32, String>,
+    next: &HashMap<u32, String>,
+    timestamp: Timestamp,
+) -> Vec<ProcessEvent> {
+    let event = |kind, pid: u32, name: &String| ProcessEvent {
+        kind,
+        pid,
+        process_name: Some(name.clone()),
+        timestamp,
+    };
+    let mut events: Vec<ProcessEvent> = previous
+        .iter()
+        .filter(|(pid, _)| !next.contains_key(pid))
+        .map(|(pid, name)| event(ProcessEventKind::Exited, *pid, name))
+        .chain(
+            next.iter()
+                .filter(|(pid, _)| !previous.contains_key(pid))
+                .map(|(pid, name)| event(ProcessEventKind::Started, *pid, name)),
+        )
+        .collect();
+    events.sort_by_key(|event| (event.kind == ProcessEventKind::Started, event.pid));
+    events
+}
+
+/// Keeps `target_pids` in sync with the processes matching `targets`, starting from
+/// `processes`, and reports each change to `events`; `label` names the targets in logs.
+fn start_pid_scanner(
+    targets: TargetSpec,
+    label: String,
+    mut processes: HashMap<u32, String>,
+    target_pids: SharedTargetPids,
+    events: Sender<ProcessEvent>,
+) {
     thread::spawn(move || {
         loop {
             thread::sleep(PID_SCAN_INTERVAL);
 
-            let next_pids = current_target_pids(&app_name);
+            let next_processes = targets.current_processes();
+            // Report starts before the new PIDs are captured, so they precede their messages.
+            for event in process_events(&processes, &next_processes, current_timestamp()) {
+                let _ = events.send(event);
+            }
+
+            let next_pids = targets.target_pids(&next_processes);
+            processes = next_processes;
             let Ok(mut pids) = target_pids.write() else {
                 break;
             };
 
             if *pids != next_pids {
                 *pids = next_pids;
-                eprintln!(
-                    "Updated process IDs for '{}': {}",
-                    app_name,
-                    format_pids(&pids)
-                );
+                eprintln!("Updated process IDs for {}: {}", label, format_pids(&pids));
             }
         }
     });
 }
 
-fn open_output(output_file: Option<&Path>, append: bool) -> io::Result<Box<dyn Write>> {
-    match output_file {
-        Some(path) => {
-            let mut options = OpenOptions::new();
-            options.create(true).write(true);
-            if append {
-                options.append(true);
-            } else {
-                options.truncate(true);
+/// Opens the output sink; files get the format header unless they already have content.
+fn open_output(output: &OutputSpec, append: bool, rotation: RotationPolicy) -> io::Result<Sink> {
+    let header = output.format.header();
+    let writer: Box<dyn Write + Send> = match output.path.as_deref() {
+        Some(path) => Box::new(BufWriter::new(RotatingFile::open(
+            path, append, rotation, header,
+        )?)),
+        None => {
+            let mut stdout = io::stdout();
+            if let Some(header) = header {
+                writeln!(stdout, "{}", header)?;
+                stdout.flush()?;
             }
-
-            let file: File = options.open(path)?;
-            Ok(Box::new(file))
+            Box::new(BufWriter::new(stdout))
         }
-        None => Ok(Box::new(io::stdout())),
-    }
+    };
+
+    Ok(Sink {
+        output: SinkOutput::Stream(writer),
+        format: output.format,
+        highlight: output.path.is_none(),
+    })
 }
 
-fn main() {
-    let program_name = env::args()
-        .next()
-        .unwrap_or_else(|| "windbgmsg".to_string());
-    let args = match get_args() {
-        Ok(args) => args,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            eprintln!("Run '{} --help' for usage.", program_name);
-            process::exit(1);
-        }
-    };
-    if args.help {
-        print_help(&program_name);
-        return;
+fn list_processes(args: &ListArgs) -> io::Result<()> {
+    let processes = list::select_processes(process_snapshot(), args.pattern.as_deref());
+    let mut stdout = io::stdout().lock();
+    if args.json {
+        list::write_json(&mut stdout, &processes, args.tree)
+    } else {
+        list::write_table(&mut stdout, &processes, args.tree)
     }
+}
 
-    let output_file = args.output_file;
-    let highlight_words = if output_file.is_none() {
-        args.highlight_words
+fn all_processes_target(exclusions: ProcessExclusions) -> CaptureTarget {
+    if exclusions.is_empty() {
+        CaptureTarget::All
     } else {
-        Vec::new()
-    };
+        CaptureTarget::AllExcept(exclusions)
+    }
+}
 
-    let mut output = match open_output(output_file.as_deref(), args.append) {
-        Ok(output) => output,
-        Err(e) => {
-            eprintln!("Error opening output file: {}", e);
-            process::exit(1);
-        }
-    };
+/// Starts the `--follow-name` / `--follow-children` scanner when requested, and returns
+/// the target to capture together with the scanner's lifecycle events.
+fn follow_target(
+    mut targets: TargetSpec,
+    processes: HashMap<u32, String>,
+    follow_name: bool,
+) -> (CaptureTarget, Option<Receiver<ProcessEvent>>) {
+    let target_pids = targets.target_pids(&processes);
+    if !follow_name && !targets.follow_children {
+        return (CaptureTarget::StaticPids(target_pids), None);
+    }
 
-    match (args.app_name, args.pid, args.wait, args.follow_name) {
-        (Some(app_name), None, true, follow_name) => {
-            let target_pids = wait_for_target_pids(&app_name);
-            println!("Process IDs: {}", format_pids(&target_pids));
-            let target = if follow_name {
-                let shared_pids = Arc::new(RwLock::new(target_pids));
-                start_pid_scanner(app_name, Arc::clone(&shared_pids));
-                CaptureTarget::SharedPids(shared_pids)
-            } else {
-                CaptureTarget::StaticPids(target_pids)
-            };
+    let label = targets.describe();
+    if !follow_name {
+        // Only children are followed: the processes found now become the fixed roots.
+        targets = TargetSpec {
+            pids: target_pids.iter().copied().collect(),
+            follow_children: true,
+            ..TargetSpec::default()
+        };
+    }
 
-            if let Err(e) = capture_debug_output(target, &mut output, &highlight_words) {
-                eprintln!("Error capturing debug output: {}", e);
-                process::exit(1);
-            }
-        }
-        (Some(app_name), None, false, follow_name) => {
-            let target_pids = current_target_pids(&app_name);
-            if target_pids.is_empty() {
-                eprintln!("Could not find process '{}'.", app_name);
-                process::exit(1);
-            }
+    let shared_pids = Arc::new(RwLock::new(target_pids));
+    let (sender, receiver) = mpsc::channel();
+    start_pid_scanner(targets, label, processes, Arc::clone(&shared_pids), sender);
+    (CaptureTarget::SharedPids(shared_pids), Some(receiver))
+}
 
-            println!("Process IDs: {}", format_pids(&target_pids));
-            let target = if follow_name {
-                let shared_pids = Arc::new(RwLock::new(target_pids));
-                start_pid_scanner(app_name, Arc::clone(&shared_pids));
-                CaptureTarget::SharedPids(shared_pids)
-            } else {
-                CaptureTarget::StaticPids(target_pids)
+/// Tracks how long none of the target processes has been running.
+struct GoneWatch {
+    grace: Duration,
+    gone_since: Option<Instant>,
+}
+
+impl GoneWatch {
+    fn new(grace: Duration) -> Self {
+        GoneWatch {
+            grace,
+            gone_since: None,
+        }
+    }
+
+    /// Returns whether capture should stop; a process that is running again, e.g. one
+    /// restarted under `--follow-name`, restarts the grace period.
+    fn update(&mut self, any_running: bool, now: Instant) -> bool {
+        if any_running {
+            self.gone_since = None;
+            return false;
+        }
+
+        let gone_since = *self.gone_since.get_or_insert(now);
+        now.duration_since(gone_since) >= self.grace
+    }
+}
+
+/// Sets the returned flag once every PID of `target` has exited for `grace`.
+fn start_exit_watcher(target: &CaptureTarget, grace: Duration) -> Arc<AtomicBool> {
+    let target_pids = match target {
+        CaptureTarget::StaticPids(pids) => Arc::new(RwLock::new(pids.clone())),
+        CaptureTarget::SharedPids(pids) => Arc::clone(pids),
+        CaptureTarget::All | CaptureTarget::AllExcept(_) => {
+            unreachable!("validated by get_args")
+        }
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = Arc::clone(&stop);
+    thread::spawn(move || {
+        let mut watch = GoneWatch::new(grace);
+        loop {
+            thread::sleep(PID_SCAN_INTERVAL);
+
+            let running: HashSet<u32> = process_snapshot()
+                .into_iter()
+                .map(|process| process.pid)
+                .collect();
+            let Ok(pids) = target_pids.read() else {
+                break;
             };
+            let any_running = pids.iter().any(|pid| running.contains(pid));
+            drop(pids);
+            if watch.update(any_running, Instant::now()) {
+                eprintln!("All target processes have exited. Stopping capture.");
+                stop_flag.store(true, Ordering::SeqCst);
+                break;
+            }
+        }
+    });
+    stop
+}
+
+fn capture_live(
+    target: CaptureTarget,
+    process_events: Option<Receiver<ProcessEvent>>,
+    stop: Option<Arc<AtomicBool>>,
+    sinks: &mut [Sink],
+    options: &CaptureOptions,
+    queue_size: usize,
+    overflow: OverflowPolicy,
+) {
+    stop_on_ctrl_c();
+    let result = DbwinSource::open().and_then(|source| {
+        let mut source: Box<dyn MessageSource> =
+            Box::new(ReassemblingSource::new(source, REASSEMBLY_WINDOW));
+        if let Some(events) = process_events {
+            source = Box::new(WithProcessEvents::new(source, events));
+        }
+        if let Some(stop) = stop {
+            source = Box::new(UntilStopped::new(source, stop, STOP_DRAIN_TIMEOUT));
+        }
+        capture_in_background(
+            source.as_mut(),
+            target,
+            sinks,
+            options,
+            queue_size,
+            overflow,
+        )
+    });
+    match result {
+        Ok(stats) => report_stats(&stats, options),
+        Err(e) => {
+            eprintln!("Error capturing debug output: {}", e);
+            capture::flush_remaining(sinks);
+            process::exit(1);
+        }
+    }
+}
+
+/// Lets Ctrl+C end capture or replay like Esc, so buffered output is still flushed.
+fn stop_on_ctrl_c() {
+    if let Err(e) = winproc::install_ctrl_c_handler() {
+        eprintln!("Error installing Ctrl+C handler: {}", e);
+    }
+}
+
+fn report_stats(stats: &CaptureStats, options: &CaptureOptions) {
+    if stats.replaced_messages > 0 {
+        eprintln!(
+            "{} message(s) needed replacement characters when decoded as {}.",
+            stats.replaced_messages, options.encoding
+        );
+    }
+    if stats.dropped_messages > 0 {
+        eprintln!(
+            "{} message(s) were dropped because the output could not keep up.",
+            stats.dropped_messages
+        );
+    }
+}
+
+fn replay_log(
+    path: &Path,
+    target: CaptureTarget,
+    sinks: &mut [Sink],
+    options: &CaptureOptions,
+    speed: Option<f64>,
+) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error opening replay file: {}", e);
+            process::exit(1);
+        }
+    };
+
+    stop_on_ctrl_c();
+    let mut source = ReplaySource::new(BufReader::new(file), speed);
+    match capture_debug_output(&mut source, target, sinks, options) {
+        Ok(stats) => report_stats(&stats, options),
+        Err(e) => {
+            eprintln!("Error replaying debug output: {}", e);
+            capture::flush_remaining(sinks);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let program_name = env::args()
+        .next()
+        .unwrap_or_else(|| "windbgmsg".to_string());
+    let args = match get_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Run '{} --help' for usage.", program_name);
+            process::exit(1);
+        }
+    };
+    if args.help {
+        print_help(&program_name);
+        return;
+    }
 
-            if let Err(e) = capture_debug_output(target, &mut output, &highlight_words) {
-                eprintln!("Error capturing debug output: {}", e);
+    if let Some(list) = args.list {
+        if let Err(e) = list_processes(&list) {
+            eprintln!("Error writing process list: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let options = CaptureOptions {
+        template: args.template.unwrap_or_default(),
+        highlight_words: args.highlight_words,
+        filter: args.filter,
+        encoding: args.encoding.unwrap_or_default(),
+        split_lines: args.split_lines,
+        flush: args.flush.unwrap_or_default(),
+    };
+
+    let mut sinks = Vec::with_capacity(args.outputs.len());
+    for output in &args.outputs {
+        match open_output(output, args.append, args.rotation) {
+            Ok(sink) => sinks.push(sink),
+            Err(e) => {
+                eprintln!("Error opening output file: {}", e);
                 process::exit(1);
             }
         }
-        (None, Some(pid), false, false) => {
-            println!("Process ID: {}", pid);
-            let mut target_pids = HashSet::new();
-            target_pids.insert(pid);
-            if let Err(e) = capture_debug_output(
-                CaptureTarget::StaticPids(target_pids),
-                &mut output,
-                &highlight_words,
-            ) {
-                eprintln!("Error capturing debug output: {}", e);
+    }
+    if let Some(split_output) = args.split_output {
+        let dir = split_output.path.expect("split output has a directory");
+        match ProcessFiles::new(
+            dir,
+            args.split_key,
+            split_output.format,
+            args.append,
+            args.rotation,
+        ) {
+            Ok(files) => sinks.push(Sink {
+                output: SinkOutput::PerProcess(Box::new(files)),
+                format: split_output.format,
+                highlight: false,
+            }),
+            Err(e) => {
+                eprintln!("Error creating --split-by-process directory: {}", e);
                 process::exit(1);
             }
         }
-        (None, None, true, false) => {
-            eprintln!("Error: --wait switch requires an app name.");
-            process::exit(1);
+    }
+
+    if let Some(replay_file) = args.replay_file {
+        let target = if args.targets.pids.is_empty() {
+            all_processes_target(args.exclusions)
+        } else {
+            CaptureTarget::StaticPids(args.targets.pids.iter().copied().collect())
+        };
+        replay_log(&replay_file, target, &mut sinks, &options, args.speed);
+        return;
+    }
+
+    let queue_size = args.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE);
+    let overflow = args.overflow.unwrap_or_default();
+    let targets = args.targets;
+    if targets.is_empty() {
+        if args.exclusions.is_empty() {
+            eprintln!("No app name provided. Capturing debug output from all processes.");
+        } else {
+            eprintln!(
+                "No app name provided. Capturing debug output from all processes except {}.",
+                args.exclusions.describe()
+            );
         }
-        (None, None, false, false) => {
-            println!("No app name provided. Capturing debug output from all processes.");
-            if let Err(e) = capture_debug_output(CaptureTarget::All, &mut output, &highlight_words)
-            {
-                eprintln!("Error capturing debug output: {}", e);
-                process::exit(1);
+        capture_live(
+            all_processes_target(args.exclusions),
+            None,
+            None,
+            &mut sinks,
+            &options,
+            queue_size,
+            overflow,
+        );
+        return;
+    }
+
+    let processes = if args.wait {
+        match wait_for_target_processes(&targets, args.wait_timeout) {
+            Some(processes) => processes,
+            None => {
+                eprintln!(
+                    "Timed out after {} waiting for process {}.",
+                    units::format_duration(args.wait_timeout.unwrap_or_default()),
+                    targets.describe_names()
+                );
+                process::exit(EXIT_WAIT_TIMEOUT);
             }
         }
-        (None, Some(_), _, true) => unreachable!("validated by get_args"),
-        (None, Some(_), true, false) => unreachable!("validated by get_args"),
-        (Some(_), Some(_), _, _) => unreachable!("validated by get_args"),
-        (None, None, _, true) => unreachable!("validated by get_args"),
+    } else {
+        targets.current_processes()
+    };
+    if targets.has_names() && processes.is_empty() {
+        eprintln!("Could not find process {}.", targets.describe_names());
+        if targets.pids.is_empty() {
+            process::exit(1);
+        }
     }
+
+    eprintln!(
+        "Process IDs: {}",
+        format_pids(&targets.target_pids(&processes))
+    );
+    let (target, process_events) = follow_target(targets, processes, args.follow_name);
+    let stop = args
+        .exit_when_gone
+        .map(|grace| start_exit_watcher(&target, grace));
+    capture_live(
+        target,
+        process_events,
+        stop,
+        &mut sinks,
+        &options,
+        queue_size,
+        overflow,
+    );
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_args;
+    use std::collections::{HashMap, HashSet};
+    use std::time::{Duration, Instant};
+
+    use super::{
+        Encoding, FlushPolicy, GoneWatch, OutputFormat, OutputSpec, OverflowPolicy, SplitKey,
+        encoding, parse_args, poll_processes, process_events,
+    };
+    use crate::source::{ProcessEventKind, test_timestamp};
 
     fn parse(args: &[&str]) -> Result<super::AppArgs, String> {
         parse_args(args.iter().map(|arg| arg.to_string()))
     }
 
+    fn file_output(path: &str, format: OutputFormat) -> OutputSpec {
+        OutputSpec {
+            path: Some(path.into()),
+            format,
+        }
+    }
+
+    fn stdout_output(format: OutputFormat) -> OutputSpec {
+        OutputSpec { path: None, format }
+    }
+
     #[test]
     fn parses_process_name() {
         let args = parse(&["notepad.exe"]).unwrap();
-        assert_eq!(args.app_name.as_deref(), Some("notepad.exe"));
-        assert_eq!(args.pid, None);
+        assert_eq!(args.targets.names, vec!["notepad.exe"]);
+        assert!(args.targets.pids.is_empty());
         assert!(!args.wait);
         assert!(!args.follow_name);
     }
@@ -385,8 +1404,8 @@ mod tests {
     #[test]
     fn parses_pid_option() {
         let args = parse(&["--pid", "1234"]).unwrap();
-        assert_eq!(args.app_name, None);
-        assert_eq!(args.pid, Some(1234));
+        assert!(args.targets.names.is_empty());
+        assert_eq!(args.targets.pids, vec![1234]);
         assert!(!args.wait);
         assert!(!args.follow_name);
     }
@@ -394,13 +1413,251 @@ mod tests {
     #[test]
     fn parses_pid_equals_option() {
         let args = parse(&["--pid=1234"]).unwrap();
-        assert_eq!(args.pid, Some(1234));
+        assert_eq!(args.targets.pids, vec![1234]);
+    }
+
+    #[test]
+    fn parses_multiple_names_and_pids() {
+        let args = parse(&[
+            "app.exe",
+            "helper.exe",
+            "--pid",
+            "77",
+            "--pid=78",
+            "APP.EXE",
+            "--pid",
+            "77",
+        ])
+        .unwrap();
+        assert_eq!(args.targets.names, vec!["app.exe", "helper.exe"]);
+        assert_eq!(args.targets.pids, vec![77, 78]);
+    }
+
+    #[test]
+    fn allows_wait_and_follow_name_with_names_and_pids() {
+        let args = parse(&["app.exe", "--pid", "77", "--wait", "--follow-name"]).unwrap();
+        assert!(args.wait);
+        assert!(args.follow_name);
+    }
+
+    #[test]
+    fn parses_name_regexes() {
+        let args = parse(&["worker-*.exe", "--name-regex", r"^svc-\d+", "--wait"]).unwrap();
+        assert_eq!(args.targets.names, vec!["worker-*.exe"]);
+        assert_eq!(args.targets.name_regexes[0].as_str(), r"^svc-\d+");
+
+        let args = parse(&["--name-regex=svc", "--follow-name"]).unwrap();
+        assert!(args.targets.has_names());
+        assert!(args.follow_name);
+    }
+
+    #[test]
+    fn rejects_invalid_name_regex() {
+        let err = parse(&["--name-regex", "svc-("]).unwrap_err();
+        assert!(err.contains("Invalid regex 'svc-('"));
+        assert!(parse(&["replay", "debug.log", "--name-regex", "svc"]).is_err());
+    }
+
+    #[test]
+    fn parses_follow_children() {
+        let args = parse(&["--pid", "77", "--follow-children"]).unwrap();
+        assert!(args.targets.follow_children);
+        assert!(!args.follow_name);
+
+        let err = parse(&["--follow-children"]).unwrap_err();
+        assert!(err.contains("--follow-children requires a process name or --pid"));
+    }
+
+    #[test]
+    fn parses_exclusions() {
+        let args = parse(&[
+            "--exclude-process",
+            "devenv.exe",
+            "--exclude-process=ServiceHub.*",
+            "--exclude-pid",
+            "4",
+            "--exclude-pid=8",
+        ])
+        .unwrap();
+        assert_eq!(args.exclusions.names, vec!["devenv.exe", "ServiceHub.*"]);
+        assert_eq!(args.exclusions.pids, HashSet::from([4, 8]));
+
+        let err = parse(&["--exclude-pid", "0"]).unwrap_err();
+        assert!(err.contains("--exclude-pid must be greater than 0"));
+    }
+
+    #[test]
+    fn rejects_exclusions_with_targets() {
+        let err = parse(&["app.exe", "--exclude-pid", "4"]).unwrap_err();
+        assert!(err.contains("only be used when capturing all processes"));
+    }
+
+    #[test]
+    fn parses_list_subcommand() {
+        let args = parse(&["list", "worker-*", "--tree", "--json"]).unwrap();
+        let list = args.list.unwrap();
+        assert_eq!(list.pattern.as_deref(), Some("worker-*"));
+        assert!(list.tree);
+        assert!(list.json);
+
+        let args = parse(&["list"]).unwrap();
+        assert_eq!(args.list.unwrap().pattern, None);
+    }
+
+    #[test]
+    fn rejects_invalid_list_arguments() {
+        let err = parse(&["list", "a", "b"]).unwrap_err();
+        assert!(err.contains("at most one pattern"));
+        let err = parse(&["list", "--wait"]).unwrap_err();
+        assert!(err.contains("Unknown list option '--wait'"));
+    }
+
+    #[test]
+    fn parses_wait_timeout() {
+        let args = parse(&["app.exe", "--wait", "--wait-timeout", "1m30s"]).unwrap();
+        assert_eq!(args.wait_timeout, Some(Duration::from_secs(90)));
+
+        let err = parse(&["app.exe", "--wait-timeout=30s"]).unwrap_err();
+        assert!(err.contains("--wait-timeout requires --wait"));
+        let err = parse(&["app.exe", "--wait", "--wait-timeout", "soon"]).unwrap_err();
+        assert!(err.contains("Invalid duration 'soon'"));
+    }
+
+    #[test]
+    fn stops_polling_after_wait_timeout() {
+        let mut polls = 0;
+        let found = poll_processes(
+            Some(Duration::ZERO),
+            Duration::ZERO,
+            || {
+                polls += 1;
+                HashMap::new()
+            },
+            |_| {},
+        );
+
+        assert_eq!(found, None);
+        assert_eq!(polls, 1);
+    }
+
+    #[test]
+    fn polls_until_processes_appear() {
+        let mut polls = 0;
+        let found = poll_processes(
+            None,
+            Duration::ZERO,
+            || {
+                polls += 1;
+                if polls < 3 {
+                    HashMap::new()
+                } else {
+                    HashMap::from([(7, "app.exe".to_string())])
+                }
+            },
+            |_| {},
+        );
+
+        assert_eq!(found, Some(HashMap::from([(7, "app.exe".to_string())])));
+    }
+
+    #[test]
+    fn parses_exit_when_gone() {
+        let args = parse(&["app.exe", "--exit-when-gone"]).unwrap();
+        assert_eq!(args.exit_when_gone, Some(Duration::ZERO));
+        let args = parse(&["--pid", "7", "--exit-when-gone=5s"]).unwrap();
+        assert_eq!(args.exit_when_gone, Some(Duration::from_secs(5)));
+
+        let err = parse(&["--exit-when-gone"]).unwrap_err();
+        assert!(err.contains("--exit-when-gone requires a process name or --pid"));
+        let err = parse(&["app.exe", "--exit-when-gone", "--exit-when-gone=5s"]).unwrap_err();
+        assert!(err.contains("--exit-when-gone can only be specified once"));
+    }
+
+    #[test]
+    fn stops_after_grace_period_without_processes() {
+        let start = Instant::now();
+        let mut watch = GoneWatch::new(Duration::from_secs(5));
+
+        assert!(!watch.update(true, start));
+        assert!(!watch.update(false, start + Duration::from_secs(1)));
+        assert!(!watch.update(false, start + Duration::from_secs(5)));
+        // A restart within the grace period starts it over.
+        assert!(!watch.update(true, start + Duration::from_secs(6)));
+        assert!(!watch.update(false, start + Duration::from_secs(7)));
+        assert!(watch.update(false, start + Duration::from_secs(12)));
+        assert!(GoneWatch::new(Duration::ZERO).update(false, start));
+    }
+
+    #[test]
+    fn parses_encoding() {
+        let args = parse(&["--encoding", "cp1252"]).unwrap();
+        assert_eq!(
+            args.encoding,
+            Some(Encoding::CodePage(encoding::CodePage::Cp1252))
+        );
+        assert_eq!(
+            parse(&["--encoding=utf8"]).unwrap().encoding,
+            Some(Encoding::Utf8)
+        );
+        assert_eq!(parse(&[]).unwrap().encoding, None);
+
+        let err = parse(&["--encoding", "ebcdic"]).unwrap_err();
+        assert!(err.contains("Unknown encoding 'ebcdic'"));
+        let args = parse(&["replay", "debug.log", "--encoding", "cp1252"]).unwrap();
+        assert_eq!(
+            args.encoding,
+            Some(Encoding::CodePage(encoding::CodePage::Cp1252))
+        );
     }
 
     #[test]
-    fn rejects_pid_with_process_name() {
-        let err = parse(&["notepad.exe", "--pid", "1234"]).unwrap_err();
-        assert!(err.contains("either a process name or --pid"));
+    fn parses_flush_policy() {
+        assert_eq!(parse(&[]).unwrap().flush, None);
+        assert_eq!(
+            parse(&["--flush", "interval=250"]).unwrap().flush,
+            Some(FlushPolicy::Interval(Duration::from_millis(250)))
+        );
+        assert_eq!(
+            parse(&["--flush=on-exit"]).unwrap().flush,
+            Some(FlushPolicy::OnExit)
+        );
+
+        let err = parse(&["--flush", "interval=0"]).unwrap_err();
+        assert!(err.contains("Invalid flush interval '0'"));
+        let err = parse(&["--flush", "never"]).unwrap_err();
+        assert!(err.contains("Unknown flush policy 'never'"));
+    }
+
+    #[test]
+    fn parses_queue_options() {
+        let args = parse(&["--queue-size", "100", "--on-overflow=drop-oldest"]).unwrap();
+        assert_eq!(args.queue_size, Some(100));
+        assert_eq!(args.overflow, Some(OverflowPolicy::DropOldest));
+
+        let err = parse(&["--queue-size", "0"]).unwrap_err();
+        assert!(err.contains("Invalid queue size '0'"));
+        let err = parse(&["--on-overflow", "drop"]).unwrap_err();
+        assert!(err.contains("Unknown overflow policy 'drop'"));
+        let err = parse(&["replay", "debug.log", "--queue-size=5"]).unwrap_err();
+        assert!(err.contains("--queue-size cannot be used with replay"));
+    }
+
+    #[test]
+    fn parses_split_lines() {
+        assert!(parse(&["--split-lines"]).unwrap().split_lines);
+        assert!(!parse(&[]).unwrap().split_lines);
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        let err = parse(&["app.exe", "--folow-name"]).unwrap_err();
+        assert!(err.contains("Unknown option '--folow-name'"));
+    }
+
+    #[test]
+    fn rejects_wait_without_process_name() {
+        let err = parse(&["--wait"]).unwrap_err();
+        assert!(err.contains("--wait requires a process name"));
     }
 
     #[test]
@@ -415,10 +1672,29 @@ mod tests {
         assert!(err.contains("Invalid PID"));
     }
 
+    #[test]
+    fn reports_started_and_exited_processes() {
+        let previous = HashMap::from([(1, "app.exe".to_string()), (2, "app.exe".to_string())]);
+        let next = HashMap::from([(2, "app.exe".to_string()), (3, "App.exe".to_string())]);
+        let events = process_events(&previous, &next, test_timestamp());
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.kind, event.pid, event.process_name.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (ProcessEventKind::Exited, 1, Some("app.exe")),
+                (ProcessEventKind::Started, 3, Some("App.exe")),
+            ]
+        );
+        assert!(process_events(&next, &next, test_timestamp()).is_empty());
+    }
+
     #[test]
     fn parses_follow_name() {
         let args = parse(&["notepad.exe", "--follow-name"]).unwrap();
-        assert_eq!(args.app_name.as_deref(), Some("notepad.exe"));
+        assert_eq!(args.targets.names, vec!["notepad.exe"]);
         assert!(args.follow_name);
     }
 
@@ -426,8 +1702,8 @@ mod tests {
     fn parses_output_option() {
         let args = parse(&["notepad.exe", "--output", "debug.log"]).unwrap();
         assert_eq!(
-            args.output_file.as_deref(),
-            Some(std::path::Path::new("debug.log"))
+            args.outputs,
+            vec![file_output("debug.log", OutputFormat::Text)]
         );
         assert!(!args.append);
     }
@@ -436,8 +1712,8 @@ mod tests {
     fn parses_output_equals_option() {
         let args = parse(&["--output=debug.log"]).unwrap();
         assert_eq!(
-            args.output_file.as_deref(),
-            Some(std::path::Path::new("debug.log"))
+            args.outputs,
+            vec![file_output("debug.log", OutputFormat::Text)]
         );
     }
 
@@ -445,8 +1721,8 @@ mod tests {
     fn parses_short_output_option() {
         let args = parse(&["-o", "debug.log"]).unwrap();
         assert_eq!(
-            args.output_file.as_deref(),
-            Some(std::path::Path::new("debug.log"))
+            args.outputs,
+            vec![file_output("debug.log", OutputFormat::Text)]
         );
     }
 
@@ -454,8 +1730,8 @@ mod tests {
     fn parses_append_with_output() {
         let args = parse(&["notepad.exe", "--output", "debug.log", "--append"]).unwrap();
         assert_eq!(
-            args.output_file.as_deref(),
-            Some(std::path::Path::new("debug.log"))
+            args.outputs,
+            vec![file_output("debug.log", OutputFormat::Text)]
         );
         assert!(args.append);
     }
@@ -496,10 +1772,128 @@ mod tests {
         assert!(err.contains("--append requires --output"));
     }
 
+    #[test]
+    fn parses_rotation_options() {
+        let args = parse(&[
+            "-o",
+            "debug.log",
+            "--rotate-size",
+            "50MB",
+            "--rotate-interval=1h",
+            "--rotate-keep",
+            "5",
+        ])
+        .unwrap();
+        assert_eq!(args.rotation.max_bytes, Some(50 * 1024 * 1024));
+        assert_eq!(args.rotation.interval, Some(Duration::from_secs(3600)));
+        assert_eq!(args.rotation.keep, Some(5));
+    }
+
+    #[test]
+    fn rejects_rotation_without_output() {
+        let err = parse(&["--rotate-size", "1MB"]).unwrap_err();
+        assert!(err.contains("require --output"));
+    }
+
+    #[test]
+    fn rejects_rotate_keep_without_trigger() {
+        let err = parse(&["-o", "debug.log", "--rotate-keep", "3"]).unwrap_err();
+        assert!(err.contains("--rotate-keep requires --rotate-size or --rotate-interval"));
+    }
+
+    #[test]
+    fn rejects_invalid_rotation_values() {
+        let err = parse(&["-o", "debug.log", "--rotate-size", "50XB"]).unwrap_err();
+        assert!(err.contains("Invalid size '50XB'"));
+        let err = parse(&["-o", "debug.log", "--rotate-interval", "0s"]).unwrap_err();
+        assert!(err.contains("--rotate-interval must be greater than 0"));
+        let err = parse(&["-o", "debug.log", "--rotate-size=1MB", "--rotate-keep=0"]).unwrap_err();
+        assert!(err.contains("--rotate-keep must be greater than 0"));
+    }
+
+    #[test]
+    fn parses_multiple_outputs_with_formats() {
+        let args = parse(&[
+            "--format",
+            "csv",
+            "-o",
+            "-",
+            "-o",
+            "text:debug.log",
+            "--output=jsonl:C:\\logs\\debug.jsonl",
+            "-o",
+            "C:\\logs\\debug.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.outputs,
+            vec![
+                stdout_output(OutputFormat::Csv),
+                file_output("debug.log", OutputFormat::Text),
+                file_output("C:\\logs\\debug.jsonl", OutputFormat::Jsonl),
+                file_output("C:\\logs\\debug.csv", OutputFormat::Csv),
+            ]
+        );
+    }
+
     #[test]
     fn rejects_duplicate_output() {
-        let err = parse(&["--output", "a.log", "--output", "b.log"]).unwrap_err();
-        assert!(err.contains("--output can only be specified once"));
+        let err = parse(&["--output", "a.log", "--output", "csv:a.log"]).unwrap_err();
+        assert!(err.contains("--output 'a.log' is specified more than once"));
+        let err = parse(&["-o", "-", "-o", "jsonl:-"]).unwrap_err();
+        assert!(err.contains("--output '-' is specified more than once"));
+    }
+
+    #[test]
+    fn rejects_output_without_path() {
+        let err = parse(&["-o", "jsonl:"]).unwrap_err();
+        assert!(err.contains("--output requires a non-empty file path"));
+    }
+
+    #[test]
+    fn allows_template_when_any_output_is_text() {
+        let args = parse(&["--format", "jsonl", "-o", "text:-", "--template", "{msg}"]).unwrap();
+        assert!(args.template.is_some());
+        let err = parse(&["-o", "csv:-", "--template", "{msg}"]).unwrap_err();
+        assert!(err.contains("--template can only be used with the text format"));
+    }
+
+    #[test]
+    fn parses_split_by_process() {
+        let args = parse(&["notepad.exe", "--follow-name", "--split-by-process", "logs"]).unwrap();
+        assert_eq!(
+            args.split_output,
+            Some(file_output("logs", OutputFormat::Text))
+        );
+        assert_eq!(args.split_key, SplitKey::Pid);
+        assert_eq!(args.outputs, vec![stdout_output(OutputFormat::Text)]);
+
+        let args = parse(&[
+            "--split-by-process=logs",
+            "--split-by-name",
+            "--format",
+            "jsonl",
+            "--append",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.split_output,
+            Some(file_output("logs", OutputFormat::Jsonl))
+        );
+        assert_eq!(args.split_key, SplitKey::Name);
+        assert!(args.append);
+    }
+
+    #[test]
+    fn rejects_split_by_name_without_directory() {
+        let err = parse(&["--split-by-name"]).unwrap_err();
+        assert!(err.contains("--split-by-name requires --split-by-process"));
+    }
+
+    #[test]
+    fn rejects_append_with_only_stdout_output() {
+        let err = parse(&["-o", "-", "--append"]).unwrap_err();
+        assert!(err.contains("--append requires --output"));
     }
 
     #[test]
@@ -508,6 +1902,163 @@ mod tests {
         assert!(err.contains("--output requires a non-empty file path"));
     }
 
+    #[test]
+    fn parses_format_option() {
+        let args = parse(&["notepad.exe", "--format", "jsonl"]).unwrap();
+        assert_eq!(args.outputs, vec![stdout_output(OutputFormat::Jsonl)]);
+
+        let args = parse(&["--format=csv"]).unwrap();
+        assert_eq!(args.outputs, vec![stdout_output(OutputFormat::Csv)]);
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        let err = parse(&["--format", "xml"]).unwrap_err();
+        assert!(err.contains("Unknown format 'xml'"));
+    }
+
+    #[test]
+    fn parses_template_option() {
+        let args = parse(&["--template", "{seq:>4} {msg}"]).unwrap();
+        assert_eq!(args.template, Some("{seq:>4} {msg}".parse().unwrap()));
+    }
+
+    #[test]
+    fn rejects_invalid_template() {
+        let err = parse(&["--template={host} {msg}"]).unwrap_err();
+        assert!(err.contains("Unknown template placeholder '{host}'"));
+    }
+
+    #[test]
+    fn rejects_template_with_structured_format() {
+        let err = parse(&["--template", "{msg}", "--format", "jsonl"]).unwrap_err();
+        assert!(err.contains("--template can only be used with the text format"));
+    }
+
+    #[test]
+    fn parses_repeated_include_options() {
+        let args = parse(&[
+            "--include",
+            "timeout",
+            "--include-regex",
+            r"^retry \d+",
+            "--include=fatal",
+        ])
+        .unwrap();
+        assert!(args.filter.matches("connection TIMEOUT"));
+        assert!(args.filter.matches("Retry 3"));
+        assert!(args.filter.matches("FATAL error"));
+        assert!(!args.filter.matches("all good"));
+    }
+
+    #[test]
+    fn applies_case_sensitive_after_include_regex() {
+        let args = parse(&["--include-regex", "Retry", "--case-sensitive"]).unwrap();
+        assert!(args.filter.matches("Retry 3"));
+        assert!(!args.filter.matches("retry 3"));
+    }
+
+    #[test]
+    fn parses_repeated_exclude_options() {
+        let args = parse(&[
+            "notepad.exe",
+            "--exclude",
+            "heartbeat",
+            "--exclude-regex=^d3d11:",
+        ])
+        .unwrap();
+        assert!(!args.filter.matches("Heartbeat sent"));
+        assert!(!args.filter.matches("D3D11: leak"));
+        assert!(args.filter.matches("request failed"));
+    }
+
+    #[test]
+    fn parses_filter_expression() {
+        let args = parse(&["--filter", "pid == 7 && !msg ~ /retry/"]).unwrap();
+        let message = crate::source::DebugMessage {
+            pid: 7,
+            timestamp: crate::source::test_timestamp(),
+            bytes: Vec::new(),
+            process_name: None,
+        };
+
+        assert!(args.filter.accepts(&message, "timeout"));
+        assert!(!args.filter.accepts(&message, "retry 3"));
+    }
+
+    #[test]
+    fn rejects_invalid_filter_expression() {
+        let err = parse(&["--filter", "pid == 1 &&"]).unwrap_err();
+        assert!(err.contains("Invalid --filter expression at column 12"));
+    }
+
+    #[test]
+    fn rejects_duplicate_filter_expression() {
+        let err = parse(&["--filter=pid == 1", "--filter=pid == 2"]).unwrap_err();
+        assert!(err.contains("--filter can only be specified once"));
+    }
+
+    #[test]
+    fn rejects_invalid_include_regex() {
+        let err = parse(&["--include-regex", "("]).unwrap_err();
+        assert!(err.contains("Invalid regex '('"));
+    }
+
+    #[test]
+    fn rejects_empty_include() {
+        let err = parse(&["--include="]).unwrap_err();
+        assert!(err.contains("--include requires a non-empty value"));
+    }
+
+    #[test]
+    fn parses_replay_subcommand() {
+        let args = parse(&["replay", "debug.log", "--pid", "1234", "--speed", "2.5"]).unwrap();
+        assert_eq!(
+            args.replay_file.as_deref(),
+            Some(std::path::Path::new("debug.log"))
+        );
+        assert!(args.targets.names.is_empty());
+        assert_eq!(args.targets.pids, vec![1234]);
+        assert_eq!(args.speed, Some(2.5));
+    }
+
+    #[test]
+    fn parses_replay_as_process_name_when_not_first() {
+        let args = parse(&["--highlight", "error", "replay"]).unwrap();
+        assert_eq!(args.targets.names, vec!["replay"]);
+        assert_eq!(args.replay_file, None);
+    }
+
+    #[test]
+    fn rejects_replay_without_file() {
+        let err = parse(&["replay"]).unwrap_err();
+        assert!(err.contains("replay requires a log file path"));
+    }
+
+    #[test]
+    fn rejects_replay_with_process_name() {
+        let err = parse(&["replay", "debug.log", "notepad.exe"]).unwrap_err();
+        assert!(err.contains("replay does not accept a process name"));
+    }
+
+    #[test]
+    fn rejects_speed_without_replay() {
+        let err = parse(&["notepad.exe", "--speed", "2"]).unwrap_err();
+        assert!(err.contains("--speed can only be used with replay"));
+    }
+
+    #[test]
+    fn rejects_non_positive_speed() {
+        let err = parse(&["replay", "debug.log", "--speed=0"]).unwrap_err();
+        assert!(err.contains("--speed must be greater than 0"));
+    }
+
+    #[test]
+    fn rejects_repeated_speed() {
+        let err = parse(&["replay", "debug.log", "--speed", "2", "--speed=4"]).unwrap_err();
+        assert!(err.contains("--speed can only be specified once"));
+    }
+
     #[test]
     fn rejects_empty_highlight() {
         let err = parse(&["--highlight=,"]).unwrap_err();
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// What a full queue does with a new item, see `--on-overflow`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the consumer to make room.
+    #[default]
+    Block,
+    /// Discard the oldest queued item to make room.
+    DropOldest,
+    /// Discard the new item.
+    DropNewest,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop-oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop-newest" => Ok(OverflowPolicy::DropNewest),
+            _ => Err(format!(
+                "Unknown overflow policy '{}'. Expected block, drop-oldest or drop-newest.",
+                value
+            )),
+        }
+    }
+}
+
+/// Why [`BoundedQueue::pop`] returned without an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopError {
+    Timeout,
+    /// The queue is closed and empty.
+    Closed,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    dropped: u64,
+}
+
+/// Queue between one producer and one consumer thread that holds at most `capacity`
+/// items and applies an [`OverflowPolicy`] when full. Items picked by
+/// [`BoundedQueue::keeping`] are never dropped and may exceed the capacity.
+pub struct BoundedQueue<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+    keep: fn(&T) -> bool,
+}
+
+impl<T> BoundedQueue<T> {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        BoundedQueue {
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                closed: false,
+                dropped: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+            keep: |_| false,
+        }
+    }
+
+    /// Always enqueues items for which `keep` returns `true`, without waiting for
+    /// room or dropping them.
+    pub fn keeping(mut self, keep: fn(&T) -> bool) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Adds `item`, or drops an item if the queue is full and the policy says so.
+    /// Returns `false` once the queue is closed.
+    pub fn push(&self, item: T) -> bool {
+        let mut state = self.lock();
+        let keep = (self.keep)(&item);
+        loop {
+            if state.closed {
+                return false;
+            }
+            if keep || state.items.len() < self.capacity {
+                break;
+            }
+            match self.policy {
+                OverflowPolicy::Block => {
+                    state = self
+                        .not_full
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                OverflowPolicy::DropOldest => {
+                    state.dropped += 1;
+                    let oldest = state.items.iter().position(|queued| !(self.keep)(queued));
+                    match oldest {
+                        Some(oldest) => {
+                            state.items.remove(oldest);
+                        }
+                        // Only kept items are queued, so the new one goes instead.
+                        None => return true,
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return true;
+                }
+            }
+        }
+
+        state.items.push_back(item);
+        drop(state);
+        self.not_empty.notify_one();
+        true
+    }
+
+    /// Waits up to `timeout` for the next item.
+    pub fn pop(&self, timeout: Duration) -> Result<T, PopError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                drop(state);
+                self.not_full.notify_one();
+                return Ok(item);
+            }
+            if state.closed {
+                return Err(PopError::Closed);
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(PopError::Timeout);
+            };
+            state = self
+                .not_empty
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// Rejects further items; queued items can still be popped.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+
+    /// Number of items discarded by the overflow policy; kept items are never counted.
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{BoundedQueue, OverflowPolicy, PopError};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn pop_all(queue: &BoundedQueue<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| queue.pop(TIMEOUT).ok()).collect()
+    }
+
+    fn drain(queue: &BoundedQueue<u32>) -> Vec<u32> {
+        queue.close();
+        pop_all(queue)
+    }
+
+    #[test]
+    fn drops_oldest_or_newest_items_when_full() {
+        let oldest = BoundedQueue::new(2, OverflowPolicy::DropOldest);
+        let newest = BoundedQueue::new(2, OverflowPolicy::DropNewest);
+        for item in 1..=4 {
+            assert!(oldest.push(item));
+            assert!(newest.push(item));
+        }
+
+        assert_eq!(oldest.dropped(), 2);
+        assert_eq!(drain(&oldest), vec![3, 4]);
+        assert_eq!(newest.dropped(), 2);
+        assert_eq!(drain(&newest), vec![1, 2]);
+    }
+
+    #[test]
+    fn keeps_picked_items_when_full() {
+        let even = |item: &u32| item.is_multiple_of(2);
+        let oldest = BoundedQueue::new(3, OverflowPolicy::DropOldest).keeping(even);
+        let newest = BoundedQueue::new(3, OverflowPolicy::DropNewest).keeping(even);
+        let blocking = BoundedQueue::new(1, OverflowPolicy::Block).keeping(even);
+        assert!(blocking.push(1));
+        for item in 1..=5 {
+            assert!(oldest.push(item));
+            assert!(newest.push(item));
+        }
+        for item in [2, 4] {
+            assert!(blocking.push(item));
+        }
+
+        assert_eq!(oldest.dropped(), 2);
+        assert_eq!(drain(&oldest), vec![2, 4, 5]);
+        assert_eq!(newest.dropped(), 1);
+        assert_eq!(drain(&newest), vec![1, 2, 3, 4]);
+        assert_eq!(drain(&blocking), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn blocks_until_consumer_makes_room() {
+        let queue = BoundedQueue::new(1, OverflowPolicy::Block);
+        let received = thread::scope(|scope| {
+            let consumer = scope.spawn(|| pop_all(&queue));
+            for item in 0..100 {
+                assert!(queue.push(item));
+            }
+            queue.close();
+            consumer.join().unwrap()
+        });
+
+        assert_eq!(received, (0..100).collect::<Vec<u32>>());
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn rejects_items_after_close() {
+        let queue = BoundedQueue::new(1, OverflowPolicy::Block);
+        assert!(queue.push(1));
+        queue.close();
+
+        assert!(!queue.push(2));
+        assert_eq!(queue.pop(TIMEOUT), Ok(1));
+        assert_eq!(queue.pop(TIMEOUT), Err(PopError::Closed));
+    }
+
+    #[test]
+    fn times_out_when_empty() {
+        let queue = BoundedQueue::<u32>::new(1, OverflowPolicy::Block);
+
+        assert_eq!(queue.pop(Duration::ZERO), Err(PopError::Timeout));
+    }
+
+    #[test]
+    fn parses_policy_names() {
+        assert_eq!("block".parse(), Ok(OverflowPolicy::Block));
+        assert_eq!("Drop-Oldest".parse(), Ok(OverflowPolicy::DropOldest));
+        assert_eq!("drop-newest".parse(), Ok(OverflowPolicy::DropNewest));
+        assert!("drop".parse::<OverflowPolicy>().is_err());
+    }
+}
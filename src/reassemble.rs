@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::capture::CaptureError;
+use crate::source::{DebugMessage, MessageSource, SourceEvent};
+
+/// Length of a chunk that `OutputDebugString` cut off: the `DBWinBuffer::data` capacity
+/// (`BUF_SIZE - 4`) minus the terminating NUL.
+pub const FULL_CHUNK_LEN: usize = 4096 - 4 - 1;
+
+#[cfg(windows)]
+const _: () = assert!(FULL_CHUNK_LEN == crate::winapi::BUF_SIZE - 4 - 1);
+
+/// How long to wait for the next chunk of a split message.
+pub const REASSEMBLY_WINDOW: Duration = Duration::from_millis(100);
+
+struct Partial {
+    message: DebugMessage,
+    last_chunk_at: Instant,
+}
+
+/// Joins the chunks of debug strings longer than the DBWIN buffer back into one message.
+///
+/// A full-size chunk is held back until the next message from the same PID, which is
+/// appended; the joined message is released with the first chunk that is not full-size.
+/// A message from another PID, the end of the source, or `window` passing without a
+/// chunk releases what was collected so far.
+pub struct ReassemblingSource<S> {
+    source: S,
+    window: Duration,
+    partial: Option<Partial>,
+    ready: VecDeque<SourceEvent>,
+}
+
+impl<S: MessageSource> ReassemblingSource<S> {
+    pub fn new(source: S, window: Duration) -> Self {
+        ReassemblingSource {
+            source,
+            window,
+            partial: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn release(&mut self) -> Option<SourceEvent> {
+        self.partial
+            .take()
+            .map(|partial| SourceEvent::Message(partial.message))
+    }
+
+    /// Returns the event to emit now, if any, for `message`.
+    fn add(&mut self, message: DebugMessage) -> Option<SourceEvent> {
+        let is_full = message.bytes.len() >= FULL_CHUNK_LEN;
+        let now = Instant::now();
+        let released = match &mut self.partial {
+            Some(partial)
+                if partial.message.pid == message.pid
+                    && now.duration_since(partial.last_chunk_at) <= self.window =>
+            {
+                partial.message.bytes.extend_from_slice(&message.bytes);
+                partial.last_chunk_at = now;
+                return if is_full { None } else { self.release() };
+            }
+            Some(_) => self.release(),
+            None => None,
+        };
+
+        if is_full {
+            self.partial = Some(Partial {
+                message,
+                last_chunk_at: now,
+            });
+            released
+        } else if let Some(released) = released {
+            self.ready.push_back(SourceEvent::Message(message));
+            Some(released)
+        } else {
+            Some(SourceEvent::Message(message))
+        }
+    }
+}
+
+impl<S: MessageSource> MessageSource for ReassemblingSource<S> {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Ok(event);
+            }
+
+            match self.source.next_event()? {
+                SourceEvent::Message(message) => {
+                    if let Some(event) = self.add(message) {
+                        return Ok(event);
+                    }
+                }
+                SourceEvent::Idle => {
+                    let expired = self
+                        .partial
+                        .as_ref()
+                        .is_some_and(|partial| partial.last_chunk_at.elapsed() >= self.window);
+                    if expired && let Some(event) = self.release() {
+                        return Ok(event);
+                    }
+                    return Ok(SourceEvent::Idle);
+                }
+                SourceEvent::Closed => {
+                    self.ready.push_back(SourceEvent::Closed);
+                    if let Some(event) = self.release() {
+                        return Ok(event);
+                    }
+                }
+                event @ SourceEvent::Process(_) => return Ok(event),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FULL_CHUNK_LEN, ReassemblingSource};
+    use crate::source::{DebugMessage, MessageSource, ScriptedSource, SourceEvent, test_timestamp};
+
+    fn chunk(pid: u32, fill: u8, len: usize) -> SourceEvent {
+        SourceEvent::Message(DebugMessage {
+            pid,
+            timestamp: test_timestamp(),
+            bytes: vec![fill; len],
+            process_name: None,
+        })
+    }
+
+    /// `(pid, length)` of every message until the source closes.
+    fn messages(events: Vec<SourceEvent>, window: Duration) -> Vec<(u32, usize)> {
+        let mut source = ReassemblingSource::new(ScriptedSource::new(events), window);
+        let mut messages = Vec::new();
+        loop {
+            match source.next_event().unwrap() {
+                SourceEvent::Message(message) => messages.push((message.pid, message.bytes.len())),
+                SourceEvent::Closed => return messages,
+                SourceEvent::Idle | SourceEvent::Process(_) => {}
+            }
+        }
+    }
+
+    #[test]
+    fn joins_full_chunks_with_the_rest() {
+        let events = vec![
+            chunk(1, b'a', FULL_CHUNK_LEN),
+            chunk(1, b'b', FULL_CHUNK_LEN),
+            chunk(1, b'c', 10),
+            chunk(1, b'd', 5),
+        ];
+
+        assert_eq!(
+            messages(events, Duration::from_secs(60)),
+            vec![(1, 2 * FULL_CHUNK_LEN + 10), (1, 5)]
+        );
+    }
+
+    #[test]
+    fn keeps_chunks_in_order() {
+        let mut source = ReassemblingSource::new(
+            ScriptedSource::new(vec![chunk(1, b'a', FULL_CHUNK_LEN), chunk(1, b'b', 1)]),
+            Duration::from_secs(60),
+        );
+        let Ok(SourceEvent::Message(message)) = source.next_event() else {
+            panic!("expected a message");
+        };
+
+        assert_eq!(message.bytes[0], b'a');
+        assert_eq!(message.bytes[FULL_CHUNK_LEN], b'b');
+    }
+
+    #[test]
+    fn releases_partial_message_on_other_pid() {
+        let events = vec![
+            chunk(1, b'a', FULL_CHUNK_LEN),
+            chunk(2, b'x', 3),
+            chunk(1, b'b', 4),
+        ];
+
+        assert_eq!(
+            messages(events, Duration::from_secs(60)),
+            vec![(1, FULL_CHUNK_LEN), (2, 3), (1, 4)]
+        );
+    }
+
+    #[test]
+    fn releases_partial_message_when_window_passes() {
+        let events = vec![
+            chunk(1, b'a', FULL_CHUNK_LEN),
+            SourceEvent::Idle,
+            chunk(1, b'b', 4),
+        ];
+
+        assert_eq!(
+            messages(events, Duration::ZERO),
+            vec![(1, FULL_CHUNK_LEN), (1, 4)]
+        );
+    }
+
+    #[test]
+    fn releases_partial_message_at_end_of_source() {
+        let events = vec![chunk(1, b'a', FULL_CHUNK_LEN)];
+
+        assert_eq!(
+            messages(events, Duration::from_secs(60)),
+            vec![(1, FULL_CHUNK_LEN)]
+        );
+    }
+}
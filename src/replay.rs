@@ -0,0 +1,279 @@
+use std::io::{self, BufRead};
+use std::str;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::capture::CaptureError;
+use crate::source::{DebugMessage, MessageSource, SourceEvent, Timestamp};
+use crate::template::{CONTINUATION_MARKER, UNKNOWN_PROCESS_NAME};
+use crate::winproc::ctrl_c_pressed;
+
+const REPLAY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Parses one `[YYYY-MM-DD HH:MM:SS.mmm] [pid name] message` line; the name is optional
+/// so logs written before it was added still replay. The message keeps its raw bytes,
+/// which are decoded like captured output.
+fn parse_log_line(line: &[u8]) -> Option<DebugMessage> {
+    let rest = line.strip_prefix(b"[")?;
+    let timestamp_end = find(rest, b"] [")?;
+    let timestamp = str::from_utf8(&rest[..timestamp_end]).ok()?;
+    let rest = &rest[timestamp_end + 3..];
+    let process_end = rest.iter().position(|&byte| byte == b']')?;
+    let process = String::from_utf8_lossy(&rest[..process_end]);
+    let message = &rest[process_end + 1..];
+    let message = message.strip_prefix(b" ").unwrap_or(message);
+    let (pid, process_name) = match process.split_once(' ') {
+        Some((pid, UNKNOWN_PROCESS_NAME)) => (pid, None),
+        Some((pid, name)) => (pid, Some(name.to_string())),
+        None => (process.as_ref(), None),
+    };
+
+    Some(DebugMessage {
+        pid: pid.parse().ok()?,
+        timestamp: timestamp.parse().ok()?,
+        bytes: message.to_vec(),
+        process_name,
+    })
+}
+
+/// The text of `message` after the continuation marker, if it continues `previous`.
+fn continuation_of<'a>(previous: &DebugMessage, message: &'a DebugMessage) -> Option<&'a [u8]> {
+    if message.pid != previous.pid || message.timestamp != previous.timestamp {
+        return None;
+    }
+    message.bytes.strip_prefix(CONTINUATION_MARKER.as_bytes())
+}
+
+fn replay_delay(previous: &Timestamp, next: &Timestamp, speed: f64) -> Option<Duration> {
+    let elapsed_ms = next.epoch_millis() - previous.epoch_millis();
+    if elapsed_ms <= 0 {
+        return None;
+    }
+
+    Some(Duration::from_secs_f64(elapsed_ms as f64 / 1_000.0 / speed))
+}
+
+/// Reads messages back from a log file written by `capture_debug_output`.
+///
+/// Lines that do not start with a timestamp and PID are continuation lines of a
+/// multi-line message and are joined back onto the preceding message, as are lines
+/// written by `--split-lines` with the same timestamp and PID and a continuation
+/// marker. Lines before the first message are skipped.
+pub struct ReplaySource<R> {
+    reader: R,
+    pending: Option<DebugMessage>,
+    speed: Option<f64>,
+    last_timestamp: Option<Timestamp>,
+    delayed: Option<(DebugMessage, Instant)>,
+}
+
+impl<R: BufRead> ReplaySource<R> {
+    /// With a `speed`, the original gaps between messages are replayed divided by it.
+    pub fn new(reader: R, speed: Option<f64>) -> Self {
+        ReplaySource {
+            reader,
+            pending: None,
+            speed,
+            last_timestamp: None,
+            delayed: None,
+        }
+    }
+
+    /// Reads the next line without its line ending; `false` at the end of the file.
+    fn read_line(&mut self, line: &mut Vec<u8>) -> io::Result<bool> {
+        line.clear();
+        if self.reader.read_until(b'\n', line)? == 0 {
+            return Ok(false);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        Ok(true)
+    }
+
+    fn next_message(&mut self) -> io::Result<Option<DebugMessage>> {
+        let mut line = Vec::new();
+        while self.read_line(&mut line)? {
+            if let Some(message) = parse_log_line(&line) {
+                if let Some(pending) = self.pending.as_mut()
+                    && let Some(rest) = continuation_of(pending, &message)
+                {
+                    pending.bytes.push(b'\n');
+                    pending.bytes.extend_from_slice(rest);
+                    continue;
+                }
+                if let Some(previous) = self.pending.replace(message) {
+                    return Ok(Some(previous));
+                }
+            } else if let Some(pending) = self.pending.as_mut() {
+                pending.bytes.push(b'\n');
+                pending.bytes.extend_from_slice(&line);
+            }
+        }
+
+        Ok(self.pending.take())
+    }
+
+    fn release_time(&mut self, timestamp: Timestamp) -> Instant {
+        let now = Instant::now();
+        let delay = match (self.speed, self.last_timestamp) {
+            (Some(speed), Some(previous)) => replay_delay(&previous, &timestamp, speed),
+            _ => None,
+        };
+
+        self.last_timestamp = Some(timestamp);
+        delay.map_or(now, |delay| now + delay)
+    }
+}
+
+impl<R: BufRead> MessageSource for ReplaySource<R> {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        if ctrl_c_pressed() {
+            eprintln!("Ctrl+C pressed. Exiting.");
+            return Ok(SourceEvent::Closed);
+        }
+
+        let (message, release_at) = match self.delayed.take() {
+            Some(delayed) => delayed,
+            None => match self.next_message()? {
+                Some(message) => {
+                    let release_at = self.release_time(message.timestamp);
+                    (message, release_at)
+                }
+                None => return Ok(SourceEvent::Closed),
+            },
+        };
+
+        // Sleep in short steps so long gaps in the log still yield idle events.
+        let remaining = release_at.saturating_duration_since(Instant::now());
+        if remaining > REPLAY_POLL_INTERVAL {
+            thread::sleep(REPLAY_POLL_INTERVAL);
+            self.delayed = Some((message, release_at));
+            return Ok(SourceEvent::Idle);
+        }
+
+        thread::sleep(remaining);
+        Ok(SourceEvent::Message(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use super::{ReplaySource, parse_log_line, replay_delay};
+    use crate::source::{MessageSource, SourceEvent, test_timestamp};
+
+    fn replay(log: &str) -> Vec<(u32, String)> {
+        let mut source = ReplaySource::new(Cursor::new(log.to_string()), None);
+        let mut messages = Vec::new();
+        while let SourceEvent::Message(message) = source.next_event().unwrap() {
+            messages.push((message.pid, String::from_utf8(message.bytes).unwrap()));
+        }
+        messages
+    }
+
+    #[test]
+    fn parses_captured_line() {
+        let message =
+            parse_log_line(b"[2026-06-01 09:08:07.006] [1234 my app.exe] hello [world]").unwrap();
+
+        assert_eq!(message.pid, 1234);
+        assert_eq!(message.timestamp, test_timestamp());
+        assert_eq!(message.process_name.as_deref(), Some("my app.exe"));
+        assert_eq!(message.bytes, b"hello [world]");
+    }
+
+    #[test]
+    fn parses_lines_with_unknown_or_missing_name() {
+        let unknown = parse_log_line(b"[2026-06-01 09:08:07.006] [1234 ?] text").unwrap();
+        let missing = parse_log_line(b"[2026-06-01 09:08:07.006] [1234] text").unwrap();
+
+        assert_eq!((unknown.pid, unknown.process_name), (1234, None));
+        assert_eq!((missing.pid, missing.process_name), (1234, None));
+        assert_eq!(missing.bytes, b"text");
+    }
+
+    #[test]
+    fn rejects_lines_without_prefix() {
+        assert!(parse_log_line(b"Process IDs: 1234").is_none());
+        assert!(parse_log_line(b"[2026-06-01 09:08:07.006] [abc] text").is_none());
+        assert!(parse_log_line(b"[yesterday] [1234] text").is_none());
+    }
+
+    #[test]
+    fn replays_messages_in_order() {
+        let log = "Process IDs: 1, 2\n\
+                   [2026-06-01 09:08:07.006] [1] first\n\
+                   [2026-06-01 09:08:07.010] [2] second\r\n";
+
+        assert_eq!(
+            replay(log),
+            vec![(1, "first".to_string()), (2, "second".to_string())]
+        );
+    }
+
+    #[test]
+    fn joins_continuation_lines() {
+        let log = "[2026-06-01 09:08:07.006] [1] first\n\
+                   second line\n\
+                   [2026-06-01 09:08:07.010] [2] next\n";
+
+        assert_eq!(
+            replay(log),
+            vec![
+                (1, "first\nsecond line".to_string()),
+                (2, "next".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_split_lines_with_continuation_marker() {
+        let log = "[2026-06-01 09:08:07.006] [1 app.exe] first\n\
+                   [2026-06-01 09:08:07.006] [1 app.exe] | second\n\
+                   [2026-06-01 09:08:07.006] [2 app.exe] | other pid\n";
+
+        assert_eq!(
+            replay(log),
+            vec![
+                (1, "first\nsecond".to_string()),
+                (2, "| other pid".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_bytes_that_are_not_utf8() {
+        let log = b"[2026-06-01 09:08:07.006] [1] caf\xe9\n\xff more\n".to_vec();
+        let mut source = ReplaySource::new(Cursor::new(log), None);
+        let SourceEvent::Message(message) = source.next_event().unwrap() else {
+            panic!("expected a message");
+        };
+
+        assert_eq!(message.bytes, b"caf\xe9\n\xff more");
+    }
+
+    #[test]
+    fn scales_delay_by_speed() {
+        let previous = test_timestamp();
+        let mut next = previous;
+        next.second += 2;
+
+        assert_eq!(
+            replay_delay(&previous, &next, 4.0),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(replay_delay(&next, &previous, 1.0), None);
+    }
+}
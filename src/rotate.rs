@@ -0,0 +1,237 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// When `--output` rolls over to a new file, and how many rolled files to keep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    pub max_bytes: Option<u64>,
+    pub interval: Option<Duration>,
+    /// Number of rolled files kept next to the active one; all are kept if unset.
+    pub keep: Option<usize>,
+}
+
+impl RotationPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.interval.is_some()
+    }
+}
+
+/// Returns the path of the `index`-th rolled file, e.g. `debug.log` -> `debug.2.log`.
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, index),
+    };
+    path.with_file_name(name)
+}
+
+/// Shifts `debug.log` to `debug.1.log`, `debug.1.log` to `debug.2.log` and so on,
+/// deleting rolled files beyond `keep`.
+fn shift_files(path: &Path, keep: Option<usize>) -> io::Result<()> {
+    let mut existing = 0;
+    while rotated_path(path, existing + 1).exists() {
+        existing += 1;
+    }
+
+    if let Some(keep) = keep {
+        for index in keep.max(1)..=existing {
+            fs::remove_file(rotated_path(path, index))?;
+        }
+        existing = existing.min(keep.saturating_sub(1));
+    }
+
+    for index in (1..=existing).rev() {
+        fs::rename(rotated_path(path, index), rotated_path(path, index + 1))?;
+    }
+    fs::rename(path, rotated_path(path, 1))
+}
+
+/// Output file that rolls over according to a [`RotationPolicy`].
+///
+/// Rollover only happens at the start of a line, so records are never split across
+/// files, and `header` is written at the top of every new or empty file.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    policy: RotationPolicy,
+    header: Option<String>,
+    bytes_written: u64,
+    opened_at: Instant,
+    at_line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn open(
+        path: &Path,
+        append: bool,
+        policy: RotationPolicy,
+        header: Option<&str>,
+    ) -> io::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.create(true).write(true);
+        if append {
+            options.append(true);
+        } else {
+            options.truncate(true);
+        }
+
+        let mut file = options.open(path)?;
+        let mut bytes_written = file.metadata()?.len();
+        if let Some(header) = header
+            && bytes_written == 0
+        {
+            writeln!(file, "{}", header)?;
+            bytes_written = header.len() as u64 + 1;
+        }
+
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            file,
+            policy,
+            header: header.map(str::to_string),
+            bytes_written,
+            opened_at: Instant::now(),
+            at_line_start: true,
+        })
+    }
+
+    fn rotation_due(&self) -> bool {
+        if self.bytes_written == 0 {
+            return false;
+        }
+
+        self.policy
+            .max_bytes
+            .is_some_and(|max_bytes| self.bytes_written >= max_bytes)
+            || self
+                .policy
+                .interval
+                .is_some_and(|interval| self.opened_at.elapsed() >= interval)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        shift_files(&self.path, self.policy.keep)?;
+        self.file = File::create(&self.path)?;
+        self.bytes_written = 0;
+        self.opened_at = Instant::now();
+        if let Some(header) = &self.header {
+            writeln!(self.file, "{}", header)?;
+            self.bytes_written = header.len() as u64 + 1;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.at_line_start && self.rotation_due() {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.bytes_written += written as u64;
+        if let Some(&last) = buf[..written].last() {
+            self.at_line_start = last == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::{RotatingFile, RotationPolicy, rotated_path};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("windbgmsg-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn names_rotated_files() {
+        assert_eq!(
+            rotated_path(Path::new("logs/debug.log"), 2),
+            Path::new("logs/debug.2.log")
+        );
+        assert_eq!(rotated_path(Path::new("debug"), 1), Path::new("debug.1"));
+    }
+
+    #[test]
+    fn rotates_by_size_at_line_boundaries() {
+        let dir = test_dir("rotate-size");
+        let path = dir.join("debug.log");
+        let policy = RotationPolicy {
+            max_bytes: Some(8),
+            ..RotationPolicy::default()
+        };
+        let mut file = RotatingFile::open(&path, false, policy, Some("h")).unwrap();
+        for line in ["first line", "second", "third"] {
+            write!(file, "{}", line).unwrap();
+            writeln!(file).unwrap();
+        }
+
+        assert_eq!(read(&rotated_path(&path, 2)), "h\nfirst line\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "h\nsecond\n");
+        assert_eq!(read(&path), "h\nthird\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_limited_number_of_rotated_files() {
+        let dir = test_dir("rotate-keep");
+        let path = dir.join("debug.log");
+        let policy = RotationPolicy {
+            max_bytes: Some(1),
+            keep: Some(2),
+            ..RotationPolicy::default()
+        };
+        let mut file = RotatingFile::open(&path, false, policy, None).unwrap();
+        for line in ["1", "2", "3", "4"] {
+            writeln!(file, "{}", line).unwrap();
+        }
+
+        assert_eq!(read(&path), "4\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "3\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "2\n");
+        assert!(!rotated_path(&path, 3).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_by_interval() {
+        let dir = test_dir("rotate-interval");
+        let path = dir.join("debug.log");
+        let policy = RotationPolicy {
+            interval: Some(Duration::ZERO),
+            ..RotationPolicy::default()
+        };
+        let mut file = RotatingFile::open(&path, false, policy, None).unwrap();
+        writeln!(file, "first").unwrap();
+        writeln!(file, "second").unwrap();
+
+        assert_eq!(read(&rotated_path(&path, 1)), "first\n");
+        assert_eq!(read(&path), "second\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,482 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use crate::capture::CaptureError;
+
+/// Local wall-clock time at which a message was received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+    pub millisecond: u16,
+    /// Offset of the local time from UTC, when known.
+    pub utc_offset_minutes: Option<i16>,
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+}
+
+impl Timestamp {
+    /// Formats as ISO 8601 with milliseconds, e.g. `2026-06-01T09:08:07.006+02:00`.
+    ///
+    /// The offset is omitted when it is not known, such as for replayed log files.
+    pub fn iso8601(&self) -> String {
+        let mut iso = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millisecond
+        );
+        match self.utc_offset_minutes {
+            Some(0) => iso.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                iso.push_str(&format!("{}{:02}:{:02}", sign, offset / 60, offset % 60));
+            }
+            None => {}
+        }
+        iso
+    }
+
+    /// Converts to UTC using the recorded offset; `None` without one, e.g. for replayed
+    /// logs, since the local time cannot be converted.
+    pub fn utc(self) -> Option<Timestamp> {
+        let offset = self.utc_offset_minutes?;
+        Some(Timestamp::from_epoch_millis(
+            self.epoch_millis() - i64::from(offset) * 60_000,
+            Some(0),
+        ))
+    }
+
+    pub fn from_epoch_millis(millis: i64, utc_offset_minutes: Option<i16>) -> Timestamp {
+        // Civil-from-days conversion, the inverse of `epoch_millis`.
+        let days = millis.div_euclid(86_400_000);
+        let millis_of_day = millis.rem_euclid(86_400_000);
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Timestamp {
+            year: year as u16,
+            month: month as u16,
+            day: day as u16,
+            hour: (millis_of_day / 3_600_000) as u16,
+            minute: (millis_of_day / 60_000 % 60) as u16,
+            second: (millis_of_day / 1_000 % 60) as u16,
+            millisecond: (millis_of_day % 1_000) as u16,
+            utc_offset_minutes,
+        }
+    }
+
+    /// Milliseconds since 1970-01-01 00:00:00.000 in the same (local) time zone.
+    pub fn epoch_millis(&self) -> i64 {
+        // Days-from-civil conversion with years starting in March.
+        let (year, month) = if self.month <= 2 {
+            (i64::from(self.year) - 1, i64::from(self.month) + 9)
+        } else {
+            (i64::from(self.year), i64::from(self.month) - 3)
+        };
+        let era = year / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * month + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let seconds = days * 86_400
+            + i64::from(self.hour) * 3_600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second);
+        seconds * 1_000 + i64::from(self.millisecond)
+    }
+}
+
+fn parse_timestamp_field(value: &str, min: u16, max: u16) -> Option<u16> {
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    value
+        .parse::<u16>()
+        .ok()
+        .filter(|field| (min..=max).contains(field))
+}
+
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid timestamp '{}'.", value);
+        let bytes = value.as_bytes();
+        if bytes.len() != 23
+            || bytes[4] != b'-'
+            || bytes[7] != b'-'
+            || bytes[10] != b' '
+            || bytes[13] != b':'
+            || bytes[16] != b':'
+            || bytes[19] != b'.'
+        {
+            return Err(invalid());
+        }
+
+        let field = |start: usize, end: usize, min: u16, max: u16| {
+            value
+                .get(start..end)
+                .and_then(|field| parse_timestamp_field(field, min, max))
+                .ok_or_else(invalid)
+        };
+
+        Ok(Timestamp {
+            year: field(0, 4, 1, 9999)?,
+            month: field(5, 7, 1, 12)?,
+            day: field(8, 10, 1, 31)?,
+            hour: field(11, 13, 0, 23)?,
+            minute: field(14, 16, 0, 59)?,
+            second: field(17, 19, 0, 59)?,
+            millisecond: field(20, 23, 0, 999)?,
+            utc_offset_minutes: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugMessage {
+    pub pid: u32,
+    pub timestamp: Timestamp,
+    pub bytes: Vec<u8>,
+    /// Executable name of the sending process, if the source could resolve it.
+    pub process_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessEventKind {
+    Started,
+    Exited,
+}
+
+impl ProcessEventKind {
+    /// Event name used in JSON records.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessEventKind::Started => "process_started",
+            ProcessEventKind::Exited => "process_exited",
+        }
+    }
+
+    /// Message text used in text records.
+    pub fn text(&self) -> &'static str {
+        match self {
+            ProcessEventKind::Started => "*** process started ***",
+            ProcessEventKind::Exited => "*** process exited ***",
+        }
+    }
+}
+
+/// A target process appeared or disappeared between two PID scans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessEvent {
+    pub kind: ProcessEventKind,
+    pub pid: u32,
+    pub process_name: Option<String>,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug)]
+pub enum SourceEvent {
+    Message(DebugMessage),
+    Process(ProcessEvent),
+    /// No message arrived before the source's poll timeout.
+    Idle,
+    /// The source is exhausted or was stopped by the user.
+    Closed,
+}
+
+pub trait MessageSource {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError>;
+}
+
+impl<S: MessageSource + ?Sized> MessageSource for Box<S> {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        (**self).next_event()
+    }
+}
+
+/// Interleaves process lifecycle events sent by a PID scanner thread with another source.
+pub struct WithProcessEvents<S> {
+    source: S,
+    events: Receiver<ProcessEvent>,
+}
+
+impl<S: MessageSource> WithProcessEvents<S> {
+    pub fn new(source: S, events: Receiver<ProcessEvent>) -> Self {
+        WithProcessEvents { source, events }
+    }
+}
+
+impl<S: MessageSource> MessageSource for WithProcessEvents<S> {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        match self.events.try_recv() {
+            Ok(event) => Ok(SourceEvent::Process(event)),
+            Err(_) => self.source.next_event(),
+        }
+    }
+}
+
+/// How long [`UntilStopped`] keeps draining a source that never goes idle, e.g. because
+/// other processes keep writing debug output.
+pub const STOP_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Closes another source once `stop` is set, after draining the messages it still
+/// has pending, i.e. at its next `Idle` or once `drain` has passed.
+pub struct UntilStopped<S> {
+    source: S,
+    stop: Arc<AtomicBool>,
+    drain: Duration,
+    stopped_at: Option<Instant>,
+}
+
+impl<S: MessageSource> UntilStopped<S> {
+    pub fn new(source: S, stop: Arc<AtomicBool>, drain: Duration) -> Self {
+        UntilStopped {
+            source,
+            stop,
+            drain,
+            stopped_at: None,
+        }
+    }
+}
+
+impl<S: MessageSource> MessageSource for UntilStopped<S> {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        if self.stopped_at.is_none() && self.stop.load(Ordering::SeqCst) {
+            self.stopped_at = Some(Instant::now());
+        }
+        if let Some(stopped_at) = self.stopped_at
+            && stopped_at.elapsed() >= self.drain
+        {
+            return Ok(SourceEvent::Closed);
+        }
+
+        match self.source.next_event()? {
+            SourceEvent::Idle if self.stopped_at.is_some() => Ok(SourceEvent::Closed),
+            event => Ok(event),
+        }
+    }
+}
+
+#[cfg(test)]
+pub struct ScriptedSource {
+    events: std::collections::VecDeque<SourceEvent>,
+}
+
+#[cfg(test)]
+impl ScriptedSource {
+    pub fn new(events: Vec<SourceEvent>) -> Self {
+        ScriptedSource {
+            events: events.into(),
+        }
+    }
+
+    pub fn messages(messages: &[(u32, &str)]) -> Self {
+        Self::new(
+            messages
+                .iter()
+                .map(|(pid, text)| {
+                    SourceEvent::Message(DebugMessage {
+                        pid: *pid,
+                        timestamp: test_timestamp(),
+                        bytes: text.as_bytes().to_vec(),
+                        process_name: None,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl MessageSource for ScriptedSource {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        Ok(self.events.pop_front().unwrap_or(SourceEvent::Closed))
+    }
+}
+
+#[cfg(test)]
+pub fn test_timestamp() -> Timestamp {
+    Timestamp {
+        year: 2026,
+        month: 6,
+        day: 1,
+        hour: 9,
+        minute: 8,
+        second: 7,
+        millisecond: 6,
+        utc_offset_minutes: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, mpsc};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{
+        MessageSource, ProcessEvent, ProcessEventKind, ScriptedSource, SourceEvent, Timestamp,
+        UntilStopped, WithProcessEvents, test_timestamp,
+    };
+
+    #[test]
+    fn yields_pending_process_events_before_messages() {
+        let (sender, receiver) = mpsc::channel();
+        let event = ProcessEvent {
+            kind: ProcessEventKind::Started,
+            pid: 7,
+            process_name: Some("app.exe".to_string()),
+            timestamp: test_timestamp(),
+        };
+        sender.send(event.clone()).unwrap();
+        let mut source = WithProcessEvents::new(ScriptedSource::messages(&[(7, "hi")]), receiver);
+
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Process(e)) if e == event));
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Message(m)) if m.pid == 7));
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Closed)));
+    }
+
+    #[test]
+    fn drains_pending_messages_before_stopping() {
+        let message = |pid| {
+            let mut source = ScriptedSource::messages(&[(pid, "text")]);
+            source.next_event().unwrap()
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut source = UntilStopped::new(
+            ScriptedSource::new(vec![
+                SourceEvent::Idle,
+                message(1),
+                message(2),
+                SourceEvent::Idle,
+                message(3),
+            ]),
+            Arc::clone(&stop),
+            Duration::from_secs(60),
+        );
+
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Idle)));
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Message(m)) if m.pid == 1));
+        stop.store(true, Ordering::SeqCst);
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Message(m)) if m.pid == 2));
+        assert!(matches!(source.next_event(), Ok(SourceEvent::Closed)));
+    }
+
+    #[test]
+    fn stops_after_drain_timeout_without_idle() {
+        let messages = vec![(1, "other process"); 1000];
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut source = UntilStopped::new(
+            ScriptedSource::messages(&messages),
+            stop,
+            Duration::from_millis(20),
+        );
+
+        let mut drained = 0;
+        while let SourceEvent::Message(_) = source.next_event().unwrap() {
+            drained += 1;
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(drained < messages.len());
+    }
+
+    #[test]
+    fn formats_timestamp_with_milliseconds() {
+        assert_eq!(test_timestamp().to_string(), "2026-06-01 09:08:07.006");
+    }
+
+    #[test]
+    fn formats_timestamp_as_iso8601() {
+        let mut timestamp = test_timestamp();
+        assert_eq!(timestamp.iso8601(), "2026-06-01T09:08:07.006");
+
+        timestamp.utc_offset_minutes = Some(0);
+        assert_eq!(timestamp.iso8601(), "2026-06-01T09:08:07.006Z");
+
+        timestamp.utc_offset_minutes = Some(330);
+        assert_eq!(timestamp.iso8601(), "2026-06-01T09:08:07.006+05:30");
+
+        timestamp.utc_offset_minutes = Some(-240);
+        assert_eq!(timestamp.iso8601(), "2026-06-01T09:08:07.006-04:00");
+    }
+
+    #[test]
+    fn parses_formatted_timestamp() {
+        let timestamp: Timestamp = "2026-06-01 09:08:07.006".parse().unwrap();
+
+        assert_eq!(timestamp, test_timestamp());
+    }
+
+    #[test]
+    fn rejects_malformed_timestamps() {
+        assert!("2026-06-01 09:08:07".parse::<Timestamp>().is_err());
+        assert!("2026-13-01 09:08:07.006".parse::<Timestamp>().is_err());
+        assert!("2026-06-01T09:08:07.006".parse::<Timestamp>().is_err());
+        assert!("2026-06-01 +9:08:07.006".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn converts_timestamp_to_epoch_millis() {
+        let epoch: Timestamp = "1970-01-01 00:00:00.000".parse().unwrap();
+        let leap_day: Timestamp = "2024-02-29 12:00:00.500".parse().unwrap();
+
+        assert_eq!(epoch.epoch_millis(), 0);
+        assert_eq!(leap_day.epoch_millis(), 1_709_208_000_500);
+        assert_eq!(test_timestamp().epoch_millis(), 1_780_304_887_006);
+    }
+
+    #[test]
+    fn converts_epoch_millis_back_to_timestamp() {
+        for value in [
+            "1970-01-01 00:00:00.000",
+            "2000-02-29 23:59:59.999",
+            "2026-06-01 09:08:07.006",
+        ] {
+            let timestamp: Timestamp = value.parse().unwrap();
+            assert_eq!(
+                Timestamp::from_epoch_millis(timestamp.epoch_millis(), None),
+                timestamp
+            );
+        }
+    }
+
+    #[test]
+    fn converts_to_utc_across_midnight() {
+        let mut timestamp: Timestamp = "2026-01-01 01:30:00.000".parse().unwrap();
+        assert_eq!(timestamp.utc(), None);
+
+        timestamp.utc_offset_minutes = Some(120);
+        let utc = timestamp.utc().unwrap();
+        assert_eq!(utc.to_string(), "2025-12-31 23:30:00.000");
+        assert_eq!(utc.utc_offset_minutes, Some(0));
+    }
+}
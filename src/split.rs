@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::format::OutputFormat;
+use crate::rotate::{RotatingFile, RotationPolicy};
+use crate::source::DebugMessage;
+use crate::template::UNKNOWN_PROCESS_NAME;
+
+/// Open file handles kept by `--split-by-process`; the least recently written one is
+/// closed beyond this, and reopened for appending if its process writes again.
+const MAX_OPEN_FILES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitKey {
+    /// One file per PID, e.g. `notepad.exe-1234.log`.
+    #[default]
+    Pid,
+    /// One file per executable name, e.g. `notepad.exe.log`.
+    Name,
+}
+
+/// Replaces characters that are not allowed in Windows file names.
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| match ch {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect()
+}
+
+pub fn split_file_name(message: &DebugMessage, key: SplitKey, format: OutputFormat) -> String {
+    let name = sanitize_file_name(
+        message
+            .process_name
+            .as_deref()
+            .unwrap_or(UNKNOWN_PROCESS_NAME),
+    );
+    match key {
+        SplitKey::Pid => format!("{}-{}.{}", name, message.pid, format.extension()),
+        SplitKey::Name => format!("{}.{}", name, format.extension()),
+    }
+}
+
+struct OpenFile {
+    file: BufWriter<RotatingFile>,
+    last_used: u64,
+}
+
+/// File chosen for a PID with [`SplitKey::Pid`].
+struct PidFile {
+    /// Name the file was chosen or confirmed with; `None` until the name resolves.
+    process_name: Option<String>,
+    file_name: String,
+}
+
+/// Output files inside the `--split-by-process` directory, opened on first use.
+pub struct ProcessFiles {
+    dir: PathBuf,
+    key: SplitKey,
+    format: OutputFormat,
+    append: bool,
+    rotation: RotationPolicy,
+    files: HashMap<String, OpenFile>,
+    /// Files already started by this run, which are appended to when reopened.
+    started: HashSet<String>,
+    /// File chosen for each PID with [`SplitKey::Pid`], kept when the process name
+    /// only resolves after its first message. Dropped when the file is closed.
+    pid_files: HashMap<u32, PidFile>,
+    writes: u64,
+}
+
+impl ProcessFiles {
+    pub fn new(
+        dir: PathBuf,
+        key: SplitKey,
+        format: OutputFormat,
+        append: bool,
+        rotation: RotationPolicy,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(ProcessFiles {
+            dir,
+            key,
+            format,
+            append,
+            rotation,
+            files: HashMap::new(),
+            started: HashSet::new(),
+            pid_files: HashMap::new(),
+            writes: 0,
+        })
+    }
+
+    /// The file for a PID, unless the PID now belongs to another executable.
+    fn pid_file_name(&mut self, message: &DebugMessage) -> String {
+        let process_name = message.process_name.as_ref();
+        if let Some(pid_file) = self.pid_files.get_mut(&message.pid)
+            && (process_name.is_none()
+                || pid_file.process_name.is_none()
+                || pid_file.process_name.as_ref() == process_name)
+        {
+            if pid_file.process_name.is_none() {
+                pid_file.process_name = process_name.cloned();
+            }
+            return pid_file.file_name.clone();
+        }
+
+        let file_name = split_file_name(message, self.key, self.format);
+        self.pid_files.insert(
+            message.pid,
+            PidFile {
+                process_name: process_name.cloned(),
+                file_name: file_name.clone(),
+            },
+        );
+        file_name
+    }
+
+    pub fn file_for(&mut self, message: &DebugMessage) -> io::Result<&mut BufWriter<RotatingFile>> {
+        let name = match self.key {
+            SplitKey::Pid => self.pid_file_name(message),
+            SplitKey::Name => split_file_name(message, self.key, self.format),
+        };
+        self.writes += 1;
+        if !self.files.contains_key(&name) {
+            if self.files.len() >= MAX_OPEN_FILES
+                && let Some(oldest) = self
+                    .files
+                    .iter()
+                    .min_by_key(|(_, open)| open.last_used)
+                    .map(|(name, _)| name.clone())
+                && let Some(mut evicted) = self.files.remove(&oldest)
+            {
+                self.pid_files
+                    .retain(|_, pid_file| pid_file.file_name != oldest);
+                evicted.file.flush()?;
+            }
+
+            let append = self.append || self.started.contains(&name);
+            let file = RotatingFile::open(
+                &self.dir.join(&name),
+                append,
+                self.rotation,
+                self.format.header(),
+            )?;
+            self.started.insert(name.clone());
+            self.files.insert(
+                name.clone(),
+                OpenFile {
+                    file: BufWriter::new(file),
+                    last_used: 0,
+                },
+            );
+        }
+
+        let open = self.files.get_mut(&name).expect("file was just opened");
+        open.last_used = self.writes;
+        Ok(&mut open.file)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for open in self.files.values_mut() {
+            open.file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::{ProcessFiles, SplitKey, split_file_name};
+    use crate::format::OutputFormat;
+    use crate::rotate::RotationPolicy;
+    use crate::source::{DebugMessage, test_timestamp};
+
+    fn message(pid: u32, process_name: Option<&str>) -> DebugMessage {
+        DebugMessage {
+            pid,
+            timestamp: test_timestamp(),
+            bytes: Vec::new(),
+            process_name: process_name.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn names_files_by_pid_or_name() {
+        let notepad = message(1234, Some("notepad.exe"));
+
+        assert_eq!(
+            split_file_name(&notepad, SplitKey::Pid, OutputFormat::Text),
+            "notepad.exe-1234.log"
+        );
+        assert_eq!(
+            split_file_name(&notepad, SplitKey::Name, OutputFormat::Jsonl),
+            "notepad.exe.jsonl"
+        );
+        assert_eq!(
+            split_file_name(&message(7, None), SplitKey::Pid, OutputFormat::Csv),
+            "_-7.csv"
+        );
+        assert_eq!(
+            split_file_name(
+                &message(7, Some("a:b?.exe")),
+                SplitKey::Name,
+                OutputFormat::Text
+            ),
+            "a_b_.exe.log"
+        );
+    }
+
+    #[test]
+    fn writes_each_process_to_its_own_file() {
+        let dir = std::env::temp_dir().join(format!("windbgmsg-split-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut files = ProcessFiles::new(
+            dir.clone(),
+            SplitKey::Pid,
+            OutputFormat::Csv,
+            false,
+            RotationPolicy::default(),
+        )
+        .unwrap();
+
+        for (pid, line) in [(1, "first"), (2, "other"), (1, "second")] {
+            let message = message(pid, Some("app.exe"));
+            writeln!(files.file_for(&message).unwrap(), "{}", line).unwrap();
+        }
+        files.flush().unwrap();
+
+        let header = "timestamp,pid,process_name,message,event";
+        assert_eq!(
+            fs::read_to_string(dir.join("app.exe-1.csv")).unwrap(),
+            format!("{}\nfirst\nsecond\n", header)
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("app.exe-2.csv")).unwrap(),
+            format!("{}\nother\n", header)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_pid_file_when_name_resolves_later() {
+        let dir = std::env::temp_dir().join(format!("windbgmsg-late-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut files = ProcessFiles::new(
+            dir.clone(),
+            SplitKey::Pid,
+            OutputFormat::Text,
+            false,
+            RotationPolicy::default(),
+        )
+        .unwrap();
+
+        for (process_name, line) in [(None, "first"), (Some("late.exe"), "second")] {
+            writeln!(
+                files.file_for(&message(3, process_name)).unwrap(),
+                "{}",
+                line
+            )
+            .unwrap();
+        }
+        files.flush().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("_-3.log")).unwrap(),
+            "first\nsecond\n"
+        );
+        assert!(!dir.join("late.exe-3.log").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn starts_new_file_when_pid_is_reused() {
+        let dir = std::env::temp_dir().join(format!("windbgmsg-reuse-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut files = ProcessFiles::new(
+            dir.clone(),
+            SplitKey::Pid,
+            OutputFormat::Text,
+            false,
+            RotationPolicy::default(),
+        )
+        .unwrap();
+
+        for (process_name, line) in [
+            (Some("old.exe"), "first"),
+            (None, "unresolved"),
+            (Some("new.exe"), "second"),
+        ] {
+            writeln!(
+                files.file_for(&message(9, process_name)).unwrap(),
+                "{}",
+                line
+            )
+            .unwrap();
+        }
+        files.flush().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("old.exe-9.log")).unwrap(),
+            "first\nunresolved\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("new.exe-9.log")).unwrap(),
+            "second\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::winproc::{ProcessInfo, process_snapshot};
+
+/// Matches `name` against a glob `pattern` where `*` is any run of characters and `?`
+/// is a single character, ignoring ASCII case.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|ch| ch.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|ch| ch.to_ascii_lowercase()).collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name position it currently stands for.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+/// Returns `roots` together with the PIDs of all their descendants in `processes`.
+fn descendants(processes: &[ProcessInfo], roots: HashSet<u32>) -> HashSet<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for process in processes {
+        // The idle process is its own parent on Windows.
+        if process.parent_pid != process.pid {
+            children
+                .entry(process.parent_pid)
+                .or_default()
+                .push(process.pid);
+        }
+    }
+
+    let mut pending: Vec<u32> = roots.iter().copied().collect();
+    let mut found = roots;
+    while let Some(pid) = pending.pop() {
+        for &child in children.get(&pid).into_iter().flatten() {
+            if found.insert(child) {
+                pending.push(child);
+            }
+        }
+    }
+    found
+}
+
+/// Processes left out of all-process capture by `--exclude-process` and `--exclude-pid`.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessExclusions {
+    /// Executable names or glob patterns, matched case-insensitively.
+    pub names: Vec<String>,
+    pub pids: HashSet<u32>,
+}
+
+impl ProcessExclusions {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.pids.is_empty()
+    }
+
+    /// Whether messages from this process are dropped; an unknown name never matches.
+    pub fn excludes(&self, pid: u32, process_name: Option<&str>) -> bool {
+        self.pids.contains(&pid)
+            || process_name
+                .is_some_and(|name| self.names.iter().any(|pattern| glob_matches(pattern, name)))
+    }
+
+    /// Quoted names and PIDs for messages, e.g. `'devenv.exe', PID 4`.
+    pub fn describe(&self) -> String {
+        let mut pids: Vec<u32> = self.pids.iter().copied().collect();
+        pids.sort_unstable();
+        self.names
+            .iter()
+            .map(|name| format!("'{}'", name))
+            .chain(pids.iter().map(|pid| format!("PID {}", pid)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Process names and PIDs given on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct TargetSpec {
+    /// Executable names or glob patterns, matched case-insensitively.
+    pub names: Vec<String>,
+    /// `--name-regex` patterns, compiled case-insensitively.
+    pub name_regexes: Vec<Regex>,
+    /// PIDs captured as given, without checking that they exist.
+    pub pids: Vec<u32>,
+    /// Also match every running descendant of the matched processes and PIDs.
+    pub follow_children: bool,
+}
+
+impl TargetSpec {
+    pub fn is_empty(&self) -> bool {
+        !self.has_names() && self.pids.is_empty()
+    }
+
+    /// Whether any name, glob or regex was given, i.e. processes have to be looked up.
+    pub fn has_names(&self) -> bool {
+        !self.names.is_empty() || !self.name_regexes.is_empty()
+    }
+
+    /// Quoted names and regexes for messages, e.g. `'app.exe', 'worker-*.exe', /svc-\d+/`.
+    pub fn describe_names(&self) -> String {
+        self.names
+            .iter()
+            .map(|name| format!("'{}'", name))
+            .chain(
+                self.name_regexes
+                    .iter()
+                    .map(|regex| format!("/{}/", regex.as_str())),
+            )
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Names, regexes and PIDs for messages, e.g. `'app.exe', PID 77`.
+    pub fn describe(&self) -> String {
+        let names = self.describe_names();
+        let pids = self.pids.iter().map(|pid| format!("PID {}", pid));
+        if names.is_empty() {
+            pids.collect::<Vec<String>>().join(", ")
+        } else {
+            std::iter::once(names)
+                .chain(pids)
+                .collect::<Vec<String>>()
+                .join(", ")
+        }
+    }
+
+    fn matches(&self, exe_name: &str) -> bool {
+        self.names.iter().any(|name| glob_matches(name, exe_name))
+            || self
+                .name_regexes
+                .iter()
+                .any(|regex| regex.is_match(exe_name))
+    }
+
+    /// Processes from `processes` whose executable name matches one of the names,
+    /// globs or regexes. With `follow_children`, the running PIDs and all descendants
+    /// of the matches are included too.
+    pub fn matching_processes(&self, processes: Vec<ProcessInfo>) -> HashMap<u32, String> {
+        let mut matched: HashSet<u32> = processes
+            .iter()
+            .filter(|process| self.matches(&process.exe_name))
+            .map(|process| process.pid)
+            .collect();
+        if self.follow_children {
+            matched.extend(&self.pids);
+            matched = descendants(&processes, matched);
+        }
+
+        processes
+            .into_iter()
+            .filter(|process| matched.contains(&process.pid))
+            .map(|process| (process.pid, process.exe_name))
+            .collect()
+    }
+
+    pub fn current_processes(&self) -> HashMap<u32, String> {
+        let follows_pids = self.follow_children && !self.pids.is_empty();
+        if !self.has_names() && !follows_pids {
+            return HashMap::new();
+        }
+
+        self.matching_processes(process_snapshot())
+    }
+
+    /// The PIDs of `processes` plus the explicit PIDs.
+    pub fn target_pids(&self, processes: &HashMap<u32, String>) -> HashSet<u32> {
+        processes.keys().chain(&self.pids).copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{ProcessExclusions, TargetSpec, glob_matches};
+    use crate::filter::build_regex;
+    use crate::winproc::ProcessInfo;
+
+    fn process(pid: u32, exe_name: &str) -> ProcessInfo {
+        child(pid, 0, exe_name)
+    }
+
+    fn child(pid: u32, parent_pid: u32, exe_name: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent_pid,
+            thread_count: 1,
+            exe_name: exe_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn matches_any_of_several_names() {
+        let spec = TargetSpec {
+            names: vec!["app.exe".to_string(), "helper.exe".to_string()],
+            pids: vec![77],
+            ..TargetSpec::default()
+        };
+        let processes = spec.matching_processes(vec![
+            process(1, "App.exe"),
+            process(2, "other.exe"),
+            process(3, "HELPER.EXE"),
+        ]);
+
+        assert_eq!(
+            processes,
+            HashMap::from([(1, "App.exe".to_string()), (3, "HELPER.EXE".to_string())])
+        );
+        assert_eq!(spec.target_pids(&processes), HashSet::from([1, 3, 77]));
+        assert_eq!(spec.describe_names(), "'app.exe', 'helper.exe'");
+        assert_eq!(spec.describe(), "'app.exe', 'helper.exe', PID 77");
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("worker-*.exe", "Worker-1.2.3.exe"));
+        assert!(glob_matches("worker-*.exe", "worker-.exe"));
+        assert!(glob_matches("app?.exe", "app7.exe"));
+        assert!(glob_matches("*", "anything.exe"));
+        assert!(glob_matches("*svc*", "my-svc-host.exe"));
+        assert!(glob_matches("notepad.exe", "NOTEPAD.EXE"));
+        assert!(!glob_matches("worker-*.exe", "worker-1.dll"));
+        assert!(!glob_matches("app?.exe", "app.exe"));
+        assert!(!glob_matches("notepad.exe", "notepad.exe.old"));
+    }
+
+    #[test]
+    fn follows_descendants_of_matches_and_pids() {
+        let spec = TargetSpec {
+            names: vec!["app.exe".to_string()],
+            pids: vec![50],
+            follow_children: true,
+            ..TargetSpec::default()
+        };
+        let processes = spec.matching_processes(vec![
+            child(1, 0, "app.exe"),
+            child(2, 1, "helper.exe"),
+            child(3, 2, "grandchild.exe"),
+            child(4, 0, "other.exe"),
+            child(50, 4, "tool.exe"),
+            child(51, 50, "tool-helper.exe"),
+        ]);
+
+        assert_eq!(
+            processes.keys().copied().collect::<HashSet<u32>>(),
+            HashSet::from([1, 2, 3, 50, 51])
+        );
+    }
+
+    #[test]
+    fn ignores_children_without_follow_children() {
+        let spec = TargetSpec {
+            names: vec!["app.exe".to_string()],
+            ..TargetSpec::default()
+        };
+        let processes =
+            spec.matching_processes(vec![child(1, 0, "app.exe"), child(2, 1, "helper.exe")]);
+
+        assert_eq!(processes.keys().copied().collect::<Vec<u32>>(), vec![1]);
+    }
+
+    #[test]
+    fn excludes_by_name_or_pid() {
+        let exclusions = ProcessExclusions {
+            names: vec!["devenv.exe".to_string(), "*helper*".to_string()],
+            pids: HashSet::from([4]),
+        };
+
+        assert!(exclusions.excludes(4, None));
+        assert!(exclusions.excludes(10, Some("DevEnv.exe")));
+        assert!(exclusions.excludes(11, Some("ServiceHub.Helper.exe")));
+        assert!(!exclusions.excludes(12, Some("app.exe")));
+        assert!(!exclusions.excludes(13, None));
+        assert_eq!(exclusions.describe(), "'devenv.exe', '*helper*', PID 4");
+    }
+
+    #[test]
+    fn matches_names_by_regex() {
+        let spec = TargetSpec {
+            names: vec!["worker-*.exe".to_string()],
+            name_regexes: vec![build_regex(r"^svc-\d+\.exe$", false).unwrap()],
+            ..TargetSpec::default()
+        };
+        let processes = spec.matching_processes(vec![
+            process(1, "worker-17.exe"),
+            process(2, "SVC-42.exe"),
+            process(3, "svc-x.exe"),
+        ]);
+
+        assert_eq!(
+            processes.keys().copied().collect::<HashSet<u32>>(),
+            HashSet::from([1, 2])
+        );
+        assert_eq!(spec.describe_names(), r"'worker-*.exe', /^svc-\d+\.exe$/");
+    }
+}
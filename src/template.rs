@@ -0,0 +1,366 @@
+use std::str::FromStr;
+
+use crate::format::Record;
+
+pub const DEFAULT_TEMPLATE: &str = "[{time}] [{pid} {name}] {msg}";
+pub const UNKNOWN_PROCESS_NAME: &str = "?";
+/// Written for `{utc}` when the offset of the local time is unknown.
+const UNKNOWN_UTC_TIME: &str = "?";
+/// Starts the `{msg}` of each continuation line written with `--split-lines`.
+pub const CONTINUATION_MARKER: &str = "| ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Time,
+    Utc,
+    Pid,
+    Name,
+    Seq,
+    Delta,
+    Msg,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "time" => Some(Field::Time),
+            "utc" => Some(Field::Utc),
+            "pid" => Some(Field::Pid),
+            "name" => Some(Field::Name),
+            "seq" => Some(Field::Seq),
+            "delta" => Some(Field::Delta),
+            "msg" => Some(Field::Msg),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, Field::Pid | Field::Seq)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Padding modifiers after the colon, e.g. `{name:<12.12}` or `{seq:06}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Spec {
+    align: Option<Align>,
+    zero_pad: bool,
+    width: usize,
+    max_width: Option<usize>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Option<Self> {
+        let mut result = Spec::default();
+        let mut rest = spec;
+        if let Some(align) = rest.chars().next().and_then(|ch| match ch {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        }) {
+            result.align = Some(align);
+            rest = &rest[1..];
+        }
+
+        if let Some(stripped) = rest.strip_prefix('0') {
+            result.zero_pad = true;
+            rest = stripped;
+        }
+
+        let (width, max_width) = match rest.split_once('.') {
+            Some((width, max_width)) => (width, Some(max_width)),
+            None => (rest, None),
+        };
+        if !width.is_empty() {
+            result.width = parse_number(width)?;
+        }
+        if let Some(max_width) = max_width {
+            result.max_width = Some(parse_number(max_width)?);
+        }
+
+        Some(result)
+    }
+
+    fn apply(&self, value: &str, numeric: bool) -> String {
+        let value: String = match self.max_width {
+            Some(max_width) => value.chars().take(max_width).collect(),
+            None => value.to_string(),
+        };
+
+        let len = value.chars().count();
+        if len >= self.width {
+            return value;
+        }
+
+        let padding = self.width - len;
+        let fill = if self.zero_pad { '0' } else { ' ' };
+        let align = self.align.unwrap_or(if numeric || self.zero_pad {
+            Align::Right
+        } else {
+            Align::Left
+        });
+        let (left, right) = match align {
+            Align::Left => (0, padding),
+            Align::Right => (padding, 0),
+            Align::Center => (padding / 2, padding - padding / 2),
+        };
+
+        let mut padded = String::with_capacity(value.len() + padding);
+        padded.extend(std::iter::repeat_n(fill, left));
+        padded.push_str(&value);
+        padded.extend(std::iter::repeat_n(fill, right));
+        padded
+    }
+}
+
+fn parse_number(value: &str) -> Option<usize> {
+    if value.bytes().all(|b| b.is_ascii_digit()) {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field, Spec),
+}
+
+/// Text line layout with `{field[:spec]}` placeholders; `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().expect("default template is valid")
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = value.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("Unclosed '{{' in template '{}'.", value))?;
+                    let placeholder = &rest[..end];
+                    let (name, spec) = match placeholder.split_once(':') {
+                        Some((name, spec)) => (name, spec),
+                        None => (placeholder, ""),
+                    };
+                    let field = Field::parse(name).ok_or_else(|| {
+                        format!(
+                            "Unknown template placeholder '{{{}}}'. Expected time, utc, pid, name, seq, delta or msg.",
+                            name
+                        )
+                    })?;
+                    let spec = Spec::parse(spec).ok_or_else(|| {
+                        format!("Invalid format spec '{}' in '{{{}}}'.", spec, placeholder)
+                    })?;
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field, spec));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => {
+                    return Err(format!(
+                        "Unmatched '}}' in template '{}'; use '}}}}' for a literal brace.",
+                        value
+                    ));
+                }
+                ch => literal.push(ch),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Template { segments })
+    }
+}
+
+fn format_delta(delta_ms: i64) -> String {
+    let sign = if delta_ms < 0 { '-' } else { '+' };
+    let delta_ms = delta_ms.unsigned_abs();
+    format!("{}{}.{:03}s", sign, delta_ms / 1_000, delta_ms % 1_000)
+}
+
+impl Template {
+    pub fn render(&self, record: &Record) -> String {
+        let mut line = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => line.push_str(text),
+                Segment::Field(field, spec) => {
+                    let value = match field {
+                        Field::Time => record.message.timestamp.to_string(),
+                        Field::Utc => record
+                            .message
+                            .timestamp
+                            .utc()
+                            .map_or_else(|| UNKNOWN_UTC_TIME.to_string(), |utc| utc.to_string()),
+                        Field::Pid => record.message.pid.to_string(),
+                        Field::Name => record
+                            .message
+                            .process_name
+                            .as_deref()
+                            .unwrap_or(UNKNOWN_PROCESS_NAME)
+                            .to_string(),
+                        Field::Seq => record.seq.to_string(),
+                        Field::Delta => format_delta(record.delta_ms),
+                        Field::Msg => record.text.to_string(),
+                    };
+                    line.push_str(&spec.apply(&value, field.is_numeric()));
+                }
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Template;
+    use crate::format::Record;
+    use crate::source::{DebugMessage, test_timestamp};
+
+    fn render(template: &str, process_name: Option<&str>) -> String {
+        let mut timestamp = test_timestamp();
+        timestamp.utc_offset_minutes = Some(120);
+        let message = DebugMessage {
+            pid: 1234,
+            timestamp,
+            bytes: Vec::new(),
+            process_name: process_name.map(str::to_string),
+        };
+        let record = Record {
+            message: &message,
+            text: "hello",
+            seq: 7,
+            delta_ms: 1_250,
+            event: None,
+        };
+        template.parse::<Template>().unwrap().render(&record)
+    }
+
+    #[test]
+    fn renders_default_template() {
+        assert_eq!(
+            Template::default().render(&Record {
+                message: &DebugMessage {
+                    pid: 1,
+                    timestamp: test_timestamp(),
+                    bytes: Vec::new(),
+                    process_name: None,
+                },
+                text: "text",
+                seq: 1,
+                delta_ms: 0,
+                event: None,
+            }),
+            "[2026-06-01 09:08:07.006] [1 ?] text"
+        );
+    }
+
+    #[test]
+    fn renders_all_placeholders() {
+        assert_eq!(
+            render(
+                "{seq} {time} {utc} {delta} {pid} {name} {msg}",
+                Some("app.exe")
+            ),
+            "7 2026-06-01 09:08:07.006 2026-06-01 07:08:07.006 +1.250s 1234 app.exe hello"
+        );
+    }
+
+    #[test]
+    fn renders_unknown_utc_time_without_offset() {
+        let record = Record {
+            message: &DebugMessage {
+                pid: 1,
+                timestamp: test_timestamp(),
+                bytes: Vec::new(),
+                process_name: None,
+            },
+            text: "text",
+            seq: 1,
+            delta_ms: 0,
+            event: None,
+        };
+
+        assert_eq!(
+            "{utc} {time}".parse::<Template>().unwrap().render(&record),
+            "? 2026-06-01 09:08:07.006"
+        );
+    }
+
+    #[test]
+    fn renders_unknown_process_name() {
+        assert_eq!(render("{name}", None), "?");
+    }
+
+    #[test]
+    fn applies_width_and_alignment() {
+        assert_eq!(render("[{pid:6}]", None), "[  1234]");
+        assert_eq!(render("[{pid:<6}]", None), "[1234  ]");
+        assert_eq!(render("[{msg:^9}]", None), "[  hello  ]");
+        assert_eq!(render("[{seq:04}]", None), "[0007]");
+        assert_eq!(render("[{name:<5.3}]", Some("app.exe")), "[app  ]");
+    }
+
+    #[test]
+    fn keeps_escaped_braces() {
+        assert_eq!(render("{{{pid}}}", None), "{1234}");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(
+            "{host}"
+                .parse::<Template>()
+                .unwrap_err()
+                .contains("Unknown template placeholder '{host}'")
+        );
+        assert!("{pid".parse::<Template>().unwrap_err().contains("Unclosed"));
+        assert!(
+            "pid}"
+                .parse::<Template>()
+                .unwrap_err()
+                .contains("Unmatched")
+        );
+        assert!(
+            "{pid:x}"
+                .parse::<Template>()
+                .unwrap_err()
+                .contains("Invalid format spec")
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+/// Parses a byte size such as `4096`, `512KB` or `50MB`; units are powers of 1024.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid size '{}'. Use e.g. 512KB, 50MB or 1GB.", value);
+    let digits_end = value
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits_end);
+    let multiplier: u64 = match unit.trim_start().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(invalid)
+}
+
+/// Parses a duration such as `30s`, `15m`, `1h30m` or `250ms`; a bare number is seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration '{}'. Use e.g. 500ms, 30s, 15m, 1h or 1d.",
+            value
+        )
+    };
+    if value.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits_end = rest
+            .find(|ch: char| !ch.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number: u64 = rest[..digits_end].parse().map_err(|_| invalid())?;
+        rest = &rest[digits_end..];
+        let unit_end = rest
+            .find(|ch: char| ch.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match rest[..unit_end].to_ascii_lowercase().as_str() {
+            "ms" => Duration::from_millis(number),
+            "s" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number.checked_mul(60).ok_or_else(invalid)?),
+            "h" => Duration::from_secs(number.checked_mul(60 * 60).ok_or_else(invalid)?),
+            "d" => Duration::from_secs(number.checked_mul(24 * 60 * 60).ok_or_else(invalid)?),
+            _ => return Err(invalid()),
+        };
+        total = total.checked_add(part).ok_or_else(invalid)?;
+        rest = &rest[unit_end..];
+    }
+
+    Ok(total)
+}
+
+/// Formats a duration the way [`parse_duration`] reads it, largest unit first, e.g.
+/// `1h30m` or `250ms`; whole units come back as typed.
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: [(&str, u128); 5] = [
+        ("d", 24 * 60 * 60 * 1000),
+        ("h", 60 * 60 * 1000),
+        ("m", 60 * 1000),
+        ("s", 1000),
+        ("ms", 1),
+    ];
+    let mut rest = duration.as_millis();
+    if rest == 0 {
+        return "0s".to_string();
+    }
+
+    let mut formatted = String::new();
+    for (unit, millis) in UNITS {
+        if rest >= millis {
+            formatted.push_str(&format!("{}{}", rest / millis, unit));
+            rest %= millis;
+        }
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{format_duration, parse_duration, parse_size};
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512KB"), Ok(512 * 1024));
+        assert_eq!(parse_size("50mb"), Ok(50 * 1024 * 1024));
+        assert_eq!(parse_size("2 GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("5TB").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2D"), Ok(Duration::from_secs(172_800)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("10w").is_err());
+        assert!(parse_duration("1.5h").is_err());
+    }
+
+    #[test]
+    fn formats_durations_as_typed() {
+        for typed in ["250ms", "30s", "15m", "1h30m", "2d", "1m5s250ms"] {
+            assert_eq!(format_duration(parse_duration(typed).unwrap()), typed);
+        }
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m30s");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+    }
+}
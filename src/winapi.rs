@@ -3,6 +3,12 @@ use std::ffi::c_void;
 #[link(name = "kernel32")]
 unsafe extern "system" {
     pub fn GetLocalTime(lpSystemTime: *mut SYSTEMTIME);
+    pub fn GetSystemTime(lpSystemTime: *mut SYSTEMTIME);
+    pub fn SystemTimeToTzSpecificLocalTime(
+        lpTimeZoneInformation: *const c_void,
+        lpUniversalTime: *const SYSTEMTIME,
+        lpLocalTime: *mut SYSTEMTIME,
+    ) -> i32;
 
     pub fn OpenEventW(dwDesiredAccess: u32, bInheritHandle: i32, lpName: *const u16)
     -> *mut c_void;
@@ -40,6 +46,14 @@ unsafe extern "system" {
     pub fn Process32FirstW(hSnapshot: *mut std::ffi::c_void, lppe: *mut PROCESSENTRY32W) -> i32;
     pub fn Process32NextW(hSnapshot: *mut std::ffi::c_void, lppe: *mut PROCESSENTRY32W) -> i32;
     pub fn CloseHandle(hObject: *mut std::ffi::c_void) -> i32;
+
+    pub fn GetOEMCP() -> u32;
+    pub fn GetACP() -> u32;
+
+    pub fn SetConsoleCtrlHandler(
+        HandlerRoutine: Option<unsafe extern "system" fn(u32) -> i32>,
+        Add: i32,
+    ) -> i32;
 }
 
 #[link(name = "user32")]
@@ -58,8 +72,10 @@ pub const WAIT_TIMEOUT: u32 = 0x00000102;
 pub const FILE_MAP_READ: u32 = 0x0004;
 pub const PAGE_READWRITE: u32 = 0x04;
 pub const VK_ESCAPE: i32 = 0x1B;
+pub const CTRL_C_EVENT: u32 = 0;
+pub const CTRL_BREAK_EVENT: u32 = 1;
 
-#[allow(non_snake_case)]
+#[allow(non_snake_case, clippy::upper_case_acronyms)]
 #[repr(C)]
 pub struct SYSTEMTIME {
     pub wYear: u16,
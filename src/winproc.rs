@@ -1,42 +1,62 @@
 // winproc.rs
 // Windows process utilities for finding process ID by name
-use std::collections::HashSet;
+use std::collections::HashMap;
+#[cfg(windows)]
 use std::ffi::{OsStr, OsString};
-use std::fmt;
-use std::io::{self, Write};
+#[cfg(windows)]
 use std::mem::zeroed;
+#[cfg(windows)]
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use crate::capture::CaptureError;
+#[cfg(windows)]
 use crate::processiter::ProcessIterator;
+#[cfg(windows)]
+use crate::source::DebugMessage;
+use crate::source::{MessageSource, SourceEvent, Timestamp};
+#[cfg(windows)]
 use crate::winapi::{
-    BUF_SIZE, CreateEventW, CreateFileMappingW, DBWIN_BUFFER, DBWIN_BUFFER_READY, DBWIN_DATA_READY,
-    DBWinBuffer, FILE_MAP_READ, GetAsyncKeyState, GetLocalTime, MapViewOfFile, OpenEventW,
-    OpenFileMappingW, PAGE_READWRITE, SYSTEMTIME, SetEvent, UnmapViewOfFile, VK_ESCAPE,
-    WAIT_OBJECT_0, WAIT_TIMEOUT, WaitForSingleObject, winapi_get_last_error,
+    BUF_SIZE, CTRL_BREAK_EVENT, CTRL_C_EVENT, CloseHandle, CreateEventW, CreateFileMappingW,
+    DBWIN_BUFFER, DBWIN_BUFFER_READY, DBWIN_DATA_READY, DBWinBuffer, FILE_MAP_READ, GetACP,
+    GetAsyncKeyState, GetLocalTime, GetOEMCP, GetSystemTime, MapViewOfFile, OpenEventW,
+    OpenFileMappingW, PAGE_READWRITE, SYSTEMTIME, SetConsoleCtrlHandler, SetEvent,
+    SystemTimeToTzSpecificLocalTime, UnmapViewOfFile, VK_ESCAPE, WAIT_OBJECT_0, WAIT_TIMEOUT,
+    WaitForSingleObject, winapi_get_last_error,
 };
 
+#[cfg(windows)]
 const CAPTURE_WAIT_TIMEOUT_MS: u32 = 100;
-const ANSI_BLUE: &str = "\x1b[34m";
-const ANSI_DEFAULT_FOREGROUND: &str = "\x1b[39m";
 
+#[cfg(windows)]
 fn to_wide(s: &str) -> Vec<u16> {
     OsStr::new(s).encode_wide().chain(Some(0)).collect()
 }
 
-pub fn find_process_ids_by_name(app_name: &str) -> Vec<u32> {
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// PID of the creating process; it may since have exited and its PID been reused.
+    pub parent_pid: u32,
+    pub thread_count: u32,
+    pub exe_name: String,
+}
+
+#[cfg(windows)]
+pub fn process_snapshot() -> Vec<ProcessInfo> {
     ProcessIterator::new()
         .map(|processes| {
             processes
-                .filter_map(|entry| {
+                .map(|entry| {
                     let exe_name: OsString = OsString::from_wide(&entry.szExeFile);
                     let exe_name = exe_name.to_string_lossy();
                     let exe_name = exe_name.trim_end_matches(char::from(0));
 
-                    if exe_name.eq_ignore_ascii_case(app_name) {
-                        Some(entry.th32ProcessID)
-                    } else {
-                        None
+                    ProcessInfo {
+                        pid: entry.th32ProcessID,
+                        parent_pid: entry.th32ParentProcessID,
+                        thread_count: entry.cntThreads,
+                        exe_name: exe_name.to_string(),
                     }
                 })
                 .collect()
@@ -44,125 +64,157 @@ pub fn find_process_ids_by_name(app_name: &str) -> Vec<u32> {
         .unwrap_or_default()
 }
 
-fn matches_target_pid(target_pids: Option<&HashSet<u32>>, pid: u32) -> bool {
-    match target_pids {
-        Some(pids) => pids.contains(&pid),
-        None => true,
-    }
+#[cfg(not(windows))]
+pub fn process_snapshot() -> Vec<ProcessInfo> {
+    Vec::new()
 }
 
-pub type SharedTargetPids = Arc<RwLock<HashSet<u32>>>;
+/// Unknown PIDs trigger at most one process snapshot per interval.
+const NAME_MISS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Cached names are re-checked after this, so a reused PID picks up its new name.
+const NAME_CACHE_MAX_AGE: Duration = Duration::from_secs(10);
 
-pub enum CaptureTarget {
-    All,
-    StaticPids(HashSet<u32>),
-    SharedPids(SharedTargetPids),
+/// Maps PIDs to executable names, taking a new process snapshot when an unknown PID is seen.
+#[derive(Default)]
+pub struct ProcessNameCache {
+    names: HashMap<u32, String>,
+    refreshed_at: Option<Instant>,
 }
 
-#[derive(Debug)]
-pub enum CaptureError {
-    Windows(u32),
-    Io(io::Error),
-}
+impl ProcessNameCache {
+    pub fn name(&mut self, pid: u32) -> Option<String> {
+        self.name_with(pid, process_snapshot)
+    }
 
-impl fmt::Display for CaptureError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            CaptureError::Windows(code) => write!(f, "Windows error {}", code),
-            CaptureError::Io(e) => write!(f, "I/O error: {}", e),
+    fn name_with(
+        &mut self,
+        pid: u32,
+        snapshot: impl FnOnce() -> Vec<ProcessInfo>,
+    ) -> Option<String> {
+        let age = self.refreshed_at.map(|at| at.elapsed());
+        let expired = age.is_none_or(|age| age >= NAME_CACHE_MAX_AGE);
+        let may_retry = age.is_none_or(|age| age >= NAME_MISS_REFRESH_INTERVAL);
+        if expired || (may_retry && !self.names.contains_key(&pid)) {
+            self.refresh(snapshot());
         }
+
+        self.names.get(&pid).cloned()
     }
-}
 
-impl From<u32> for CaptureError {
-    fn from(value: u32) -> Self {
-        CaptureError::Windows(value)
+    /// Names of exited processes are kept, since their last messages may still be pending.
+    fn refresh(&mut self, processes: Vec<ProcessInfo>) {
+        self.names.extend(
+            processes
+                .into_iter()
+                .map(|process| (process.pid, process.exe_name)),
+        );
+        self.refreshed_at = Some(Instant::now());
     }
 }
 
-impl From<io::Error> for CaptureError {
-    fn from(value: io::Error) -> Self {
-        CaptureError::Io(value)
-    }
+#[cfg(windows)]
+fn escape_is_pressed() -> bool {
+    unsafe { GetAsyncKeyState(VK_ESCAPE) < 0 }
 }
 
-impl CaptureTarget {
-    fn matches_pid(&self, pid: u32) -> bool {
-        match self {
-            CaptureTarget::All => true,
-            CaptureTarget::StaticPids(pids) => matches_target_pid(Some(pids), pid),
-            CaptureTarget::SharedPids(pids) => pids
-                .read()
-                .map(|pids| matches_target_pid(Some(&pids), pid))
-                .unwrap_or(false),
-        }
+static CTRL_C_PRESSED: AtomicBool = AtomicBool::new(false);
+
+/// Turns Ctrl+C and Ctrl+Break into a request to stop capturing, so that pending
+/// output is still written and flushed.
+#[cfg(windows)]
+unsafe extern "system" fn handle_console_ctrl(ctrl_type: u32) -> i32 {
+    if ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT {
+        CTRL_C_PRESSED.store(true, Ordering::SeqCst);
+        1
+    } else {
+        0
     }
 }
 
-fn escape_is_pressed() -> bool {
-    unsafe { GetAsyncKeyState(VK_ESCAPE) < 0 }
+/// Makes Ctrl+C and Ctrl+Break stop capture or replay through [`ctrl_c_pressed`]
+/// instead of terminating the process.
+#[cfg(windows)]
+pub fn install_ctrl_c_handler() -> Result<(), CaptureError> {
+    if unsafe { SetConsoleCtrlHandler(Some(handle_console_ctrl), 1) } == 0 {
+        return Err(winapi_get_last_error().into());
+    }
+    Ok(())
 }
 
-fn format_timestamp(time: &SYSTEMTIME) -> String {
-    format!(
-        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
-        time.wYear,
-        time.wMonth,
-        time.wDay,
-        time.wHour,
-        time.wMinute,
-        time.wSecond,
-        time.wMilliseconds
-    )
+#[cfg(not(windows))]
+pub fn install_ctrl_c_handler() -> Result<(), CaptureError> {
+    Ok(())
 }
 
-fn current_timestamp() -> String {
-    unsafe {
-        let mut time: SYSTEMTIME = zeroed();
-        GetLocalTime(&mut time);
-        format_timestamp(&time)
-    }
+pub fn ctrl_c_pressed() -> bool {
+    CTRL_C_PRESSED.load(Ordering::SeqCst)
 }
 
-fn highlight_text(text: &str, words: &[String]) -> String {
-    if words.is_empty() {
-        return text.to_string();
+#[cfg(windows)]
+impl From<&SYSTEMTIME> for Timestamp {
+    fn from(time: &SYSTEMTIME) -> Self {
+        Timestamp {
+            year: time.wYear,
+            month: time.wMonth,
+            day: time.wDay,
+            hour: time.wHour,
+            minute: time.wMinute,
+            second: time.wSecond,
+            millisecond: time.wMilliseconds,
+            utc_offset_minutes: None,
+        }
     }
+}
 
-    let mut highlighted = String::with_capacity(text.len());
-    let mut index = 0;
-    while index < text.len() {
-        let best_match = words
-            .iter()
-            .filter(|word| !word.is_empty())
-            .filter_map(|word| {
-                let end = index.checked_add(word.len())?;
-                if end <= text.len()
-                    && text.is_char_boundary(end)
-                    && text[index..end].eq_ignore_ascii_case(word)
-                {
-                    Some(end)
-                } else {
-                    None
-                }
-            })
-            .max_by_key(|end| end - index);
-
-        if let Some(end) = best_match {
-            highlighted.push_str(ANSI_BLUE);
-            highlighted.push_str(&text[index..end]);
-            highlighted.push_str(ANSI_DEFAULT_FOREGROUND);
-            index = end;
-        } else {
-            let ch = text[index..].chars().next().expect("index is in bounds");
-            highlighted.push(ch);
-            index += ch.len_utf8();
+#[cfg(windows)]
+pub fn current_timestamp() -> Timestamp {
+    unsafe {
+        let mut utc: SYSTEMTIME = zeroed();
+        let mut local: SYSTEMTIME = zeroed();
+        GetSystemTime(&mut utc);
+        if SystemTimeToTzSpecificLocalTime(std::ptr::null(), &utc, &mut local) == 0 {
+            GetLocalTime(&mut local);
+            return Timestamp::from(&local);
         }
+
+        let mut timestamp = Timestamp::from(&local);
+        let offset_ms = timestamp.epoch_millis() - Timestamp::from(&utc).epoch_millis();
+        timestamp.utc_offset_minutes = Some((offset_ms / 60_000) as i16);
+        timestamp
     }
+}
+
+#[cfg(not(windows))]
+pub fn current_timestamp() -> Timestamp {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64);
+    Timestamp::from_epoch_millis(millis, Some(0))
+}
+
+/// Code page of console programs, used by `--encoding oem`.
+#[cfg(windows)]
+pub fn oem_code_page() -> u32 {
+    unsafe { GetOEMCP() }
+}
+
+#[cfg(not(windows))]
+pub fn oem_code_page() -> u32 {
+    437
+}
+
+/// Code page of non-Unicode GUI programs, used by `--encoding ansi`.
+#[cfg(windows)]
+pub fn ansi_code_page() -> u32 {
+    unsafe { GetACP() }
+}
 
-    highlighted
+#[cfg(not(windows))]
+pub fn ansi_code_page() -> u32 {
+    1252
 }
 
+#[cfg(windows)]
 fn open_or_create_event(name: &str) -> Result<*mut std::ffi::c_void, u32> {
     unsafe {
         let event = OpenEventW(0x1F0003, 0, to_wide(name).as_ptr());
@@ -179,6 +231,7 @@ fn open_or_create_event(name: &str) -> Result<*mut std::ffi::c_void, u32> {
     }
 }
 
+#[cfg(windows)]
 fn open_or_create_file_mapping(name: &str) -> Result<*mut std::ffi::c_void, u32> {
     unsafe {
         let file_mapping = OpenFileMappingW(FILE_MAP_READ, 0, to_wide(name).as_ptr());
@@ -201,97 +254,171 @@ fn open_or_create_file_mapping(name: &str) -> Result<*mut std::ffi::c_void, u32>
     }
 }
 
-pub fn capture_debug_output(
-    target: CaptureTarget,
-    output: &mut dyn Write,
-    highlight_words: &[String],
-) -> Result<(), CaptureError> {
-    unsafe {
+/// Reads `OutputDebugString` messages through the `DBWIN_BUFFER` shared-memory handshake.
+#[cfg(windows)]
+pub struct DbwinSource {
+    ready_event: *mut std::ffi::c_void,
+    data_event: *mut std::ffi::c_void,
+    file_mapping: *mut std::ffi::c_void,
+    buffer_ptr: *mut std::ffi::c_void,
+}
+
+#[cfg(not(windows))]
+pub struct DbwinSource;
+
+#[cfg(windows)]
+impl DbwinSource {
+    pub fn open() -> Result<Self, CaptureError> {
         // Try to open or create events and file mapping
         let ready_event = open_or_create_event(DBWIN_BUFFER_READY)?;
         let data_event = open_or_create_event(DBWIN_DATA_READY)?;
         let file_mapping = open_or_create_file_mapping(DBWIN_BUFFER)?;
 
-        let buffer_ptr = MapViewOfFile(file_mapping, FILE_MAP_READ, 0, 0, BUF_SIZE);
+        let buffer_ptr = unsafe { MapViewOfFile(file_mapping, FILE_MAP_READ, 0, 0, BUF_SIZE) };
         if buffer_ptr.is_null() {
             return Err(winapi_get_last_error().into());
         }
 
-        let dbwin_buffer: *const DBWinBuffer = buffer_ptr as *const DBWinBuffer;
-        loop {
-            if escape_is_pressed() {
-                eprintln!("Escape pressed. Exiting.");
-                break;
-            }
+        // Let the first writer in; afterwards the buffer is released as soon as a
+        // message has been copied out of it.
+        unsafe { SetEvent(ready_event) };
+        Ok(DbwinSource {
+            ready_event,
+            data_event,
+            file_mapping,
+            buffer_ptr,
+        })
+    }
+}
 
-            SetEvent(ready_event);
-            let wait_result = WaitForSingleObject(data_event, CAPTURE_WAIT_TIMEOUT_MS);
+#[cfg(not(windows))]
+impl DbwinSource {
+    pub fn open() -> Result<Self, CaptureError> {
+        Err(CaptureError::Unsupported)
+    }
+}
+
+#[cfg(windows)]
+impl MessageSource for DbwinSource {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        if escape_is_pressed() {
+            eprintln!("Escape pressed. Exiting.");
+            return Ok(SourceEvent::Closed);
+        }
+        if ctrl_c_pressed() {
+            eprintln!("Ctrl+C pressed. Exiting.");
+            return Ok(SourceEvent::Closed);
+        }
+
+        unsafe {
+            let wait_result = WaitForSingleObject(self.data_event, CAPTURE_WAIT_TIMEOUT_MS);
             if wait_result == WAIT_OBJECT_0 {
+                let dbwin_buffer = self.buffer_ptr as *const DBWinBuffer;
+                let msg = &(*dbwin_buffer).data;
+                let nul_pos = msg.iter().position(|&c| c == 0).unwrap_or(msg.len());
                 let pid = (*dbwin_buffer).process_id;
-                if target.matches_pid(pid) {
-                    let msg = &(*dbwin_buffer).data;
-                    let nul_pos = msg.iter().position(|&c| c == 0).unwrap_or(msg.len());
-                    let msg = &msg[..nul_pos];
-                    if let Ok(s) = std::str::from_utf8(msg) {
-                        let line = format!("[{}] [{}] {}", current_timestamp(), pid, s.trim_end());
-                        writeln!(output, "{}", highlight_text(&line, highlight_words))?;
-                        output.flush()?;
-                    }
-                }
+                let timestamp = current_timestamp();
+                let bytes = msg[..nul_pos].to_vec();
+                SetEvent(self.ready_event);
+                // Names are looked up by the writer, so a process snapshot never holds
+                // up the writing process.
+                Ok(SourceEvent::Message(DebugMessage {
+                    pid,
+                    timestamp,
+                    bytes,
+                    process_name: None,
+                }))
             } else if wait_result == WAIT_TIMEOUT {
-                continue;
+                Ok(SourceEvent::Idle)
             } else {
                 eprintln!("WaitForSingleObject failed.");
-                break;
+                Ok(SourceEvent::Closed)
             }
         }
-        UnmapViewOfFile(buffer_ptr);
-        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+impl MessageSource for DbwinSource {
+    fn next_event(&mut self) -> Result<SourceEvent, CaptureError> {
+        Ok(SourceEvent::Closed)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for DbwinSource {
+    fn drop(&mut self) {
+        unsafe {
+            UnmapViewOfFile(self.buffer_ptr);
+            CloseHandle(self.file_mapping);
+            CloseHandle(self.data_event);
+            CloseHandle(self.ready_event);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{SYSTEMTIME, format_timestamp, highlight_text};
+    use super::{ProcessInfo, ProcessNameCache};
 
     #[test]
-    fn formats_timestamp_with_milliseconds() {
-        let time = SYSTEMTIME {
-            wYear: 2026,
-            wMonth: 6,
-            wDayOfWeek: 1,
-            wDay: 1,
-            wHour: 9,
-            wMinute: 8,
-            wSecond: 7,
-            wMilliseconds: 6,
-        };
-
-        assert_eq!(format_timestamp(&time), "2026-06-01 09:08:07.006");
+    fn keeps_names_of_processes_missing_from_later_snapshots() {
+        let mut cache = ProcessNameCache::default();
+        cache.refresh(vec![ProcessInfo {
+            pid: 1,
+            parent_pid: 0,
+            thread_count: 1,
+            exe_name: "first.exe".to_string(),
+        }]);
+        cache.refresh(vec![ProcessInfo {
+            pid: 2,
+            parent_pid: 0,
+            thread_count: 1,
+            exe_name: "second.exe".to_string(),
+        }]);
+
+        assert_eq!(cache.name(1).as_deref(), Some("first.exe"));
+        assert_eq!(cache.name(2).as_deref(), Some("second.exe"));
     }
 
     #[test]
-    fn highlights_matching_words_case_insensitively() {
-        let words = vec!["error".to_string()];
+    fn limits_snapshots_for_unknown_pids() {
+        let mut cache = ProcessNameCache::default();
+        let mut snapshots = 0;
+        let mut snapshot = || {
+            snapshots += 1;
+            vec![ProcessInfo {
+                pid: 1,
+                parent_pid: 0,
+                thread_count: 1,
+                exe_name: "first.exe".to_string(),
+            }]
+        };
 
         assert_eq!(
-            highlight_text("An ERROR occurred", &words),
-            "An \x1b[34mERROR\x1b[39m occurred"
+            cache.name_with(1, &mut snapshot).as_deref(),
+            Some("first.exe")
         );
-    }
-
-    #[test]
-    fn highlights_longest_matching_word_first() {
-        let words = vec!["error".to_string(), "error code".to_string()];
-
         assert_eq!(
-            highlight_text("error code 5", &words),
-            "\x1b[34merror code\x1b[39m 5"
+            cache.name_with(1, &mut snapshot).as_deref(),
+            Some("first.exe")
         );
+        assert_eq!(cache.name_with(5, &mut snapshot), None);
+        assert_eq!(snapshots, 1);
     }
 
     #[test]
-    fn leaves_text_unchanged_without_highlight_words() {
-        assert_eq!(highlight_text("plain output", &[]), "plain output");
+    fn picks_up_reused_pids_on_refresh() {
+        let mut cache = ProcessNameCache::default();
+        let process = |exe_name: &str| ProcessInfo {
+            pid: 1,
+            parent_pid: 0,
+            thread_count: 1,
+            exe_name: exe_name.to_string(),
+        };
+        cache.refresh(vec![process("old.exe")]);
+        cache.refresh(vec![process("new.exe")]);
+
+        assert_eq!(cache.name(1).as_deref(), Some("new.exe"));
     }
 }
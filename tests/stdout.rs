@@ -0,0 +1,53 @@
+//! Checks that status lines never end up in formatted output written to stdout.
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_windbgmsg"))
+        .args(args)
+        .output()
+        .expect("windbgmsg runs")
+}
+
+#[test]
+fn writes_only_records_to_stdout() {
+    // PID 1 is not a Windows process, so capture stops on its own there; elsewhere
+    // capture fails to start after the status lines are written.
+    let csv = run(&["--pid", "1", "--exit-when-gone", "--format", "csv"]);
+
+    assert_eq!(
+        String::from_utf8_lossy(&csv.stdout),
+        "timestamp,pid,process_name,message,event\n"
+    );
+    assert!(String::from_utf8_lossy(&csv.stderr).contains("Process IDs: 1"));
+}
+
+#[test]
+fn keeps_formatted_stdout_clean_next_to_file_output() {
+    let log = std::env::temp_dir().join(format!("windbgmsg-stdout-{}.log", std::process::id()));
+    let log_arg = log.to_string_lossy().into_owned();
+    let output = run(&[
+        "--pid",
+        "1",
+        "--exit-when-gone",
+        "-o",
+        "csv:-",
+        "-o",
+        &log_arg,
+    ]);
+    let _ = std::fs::remove_file(&log);
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "timestamp,pid,process_name,message,event\n"
+    );
+}
+
+#[cfg(not(windows))]
+#[test]
+fn writes_all_process_banner_to_stderr() {
+    let jsonl = run(&["--format", "jsonl"]);
+
+    assert_eq!(String::from_utf8_lossy(&jsonl.stdout), "");
+    assert!(String::from_utf8_lossy(&jsonl.stderr).contains("No app name provided"));
+}